@@ -1,28 +1,524 @@
-use candid::{CandidType, Deserialize, Encode};
-use ic_cdk::api::{time};
-use ic_cdk_macros::{query, update};
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_cdk::api::management_canister::main::raw_rand;
+#[cfg(not(test))]
+use ic_cdk::api::{caller, is_controller, time};
+#[cfg(test)]
+use test_env::{caller, is_controller, time};
+use ic_cdk_macros::{heartbeat, init, post_upgrade, pre_upgrade, query, update};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::writer::Writer;
+use ic_stable_structures::{DefaultMemoryImpl, Memory as _, StableBTreeMap, StableLog, Storable};
 use serde::Serialize;
-use std::cell::RefCell;
-use std::collections::{HashMap};
+use std::borrow::{Borrow, Cow};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 type ItemId = u64;
 
-type Memory<T> = RefCell<HashMap<ItemId, T>>;
+type Memory<T> = RefCell<BTreeMap<ItemId, T>>;
+type StableMemory = VirtualMemory<DefaultMemoryImpl>;
+type ApiResult<T> = Result<T, BusinessError>;
+
+// Every fallible endpoint fails with one of these; the message is for people, the variant is for clients to match on.
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+enum BusinessError {
+    NotFound(String),
+    InvalidInput(String),
+    Unauthorized(String),
+    InsufficientStock(String),
+    Conflict(String),
+    MarginTooLow(String),
+    RefundExceedsPayment(String),
+    ZeroQuantity(String),
+    NothingToUpdate,
+    TooManyLines { max: u64 },
+    ConcurrentModification { current: u64 },
+    ExceedsCapacity { max: u64, attempted: u64 },
+    ValueTooLarge(String),
+    InvalidTimeRange(String),
+    InvalidBarcode(String),
+    BelowMinimumSale { total: f64, minimum: f64 },
+    RateLimited { retry_after: u64 },
+}
+
+impl std::fmt::Display for BusinessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BusinessError::NotFound(message)
+            | BusinessError::InvalidInput(message)
+            | BusinessError::Unauthorized(message)
+            | BusinessError::InsufficientStock(message)
+            | BusinessError::Conflict(message)
+            | BusinessError::MarginTooLow(message)
+            | BusinessError::RefundExceedsPayment(message)
+            | BusinessError::ZeroQuantity(message)
+            | BusinessError::ValueTooLarge(message)
+            | BusinessError::InvalidTimeRange(message)
+            | BusinessError::InvalidBarcode(message) => f.write_str(message),
+            BusinessError::NothingToUpdate => f.write_str("Nothing to update."),
+            BusinessError::TooManyLines { max } => write!(f, "A sale or cart cannot contain more than {} lines.", max),
+            BusinessError::ConcurrentModification { current } => {
+                write!(f, "The quantity was modified concurrently; current quantity is {}.", current)
+            }
+            BusinessError::ExceedsCapacity { max, attempted } => {
+                write!(f, "Quantity {} exceeds the capacity of {}.", attempted, max)
+            }
+            BusinessError::BelowMinimumSale { total, minimum } => write!(
+                f,
+                "Sale total {} is below the minimum sale amount of {}.",
+                format_amount(*total),
+                format_amount(*minimum)
+            ),
+            BusinessError::RateLimited { retry_after } => {
+                write!(f, "Rate limit exceeded; retry after {} seconds.", retry_after)
+            }
+        }
+    }
+}
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 struct InventoryItem {
     id: ItemId,
     name: String,
+    #[serde(default)]
+    variant: Option<String>,
+    quantity: u64,
+    price: f64,
+    sku: Option<String>,
+    description: Option<String>,
+    reorder_point: Option<u64>,
+    target_stock: Option<u64>,
+    max_stock: Option<u64>,
+    category: Option<String>,
+    tags: Vec<String>,
+    cost_price: Option<f64>,
+    #[serde(default)]
+    tax_exempt: bool,
+    #[serde(default = "default_active")]
+    active: bool,
+    #[serde(default)]
+    lead_time_days: Option<u64>,
+    #[serde(default)]
+    safety_stock: Option<u64>,
+    #[serde(default)]
+    serialized: bool,
+    #[serde(default)]
+    online_buffer: Option<u64>,
+    #[serde(default)]
+    deposit: Option<f64>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug)]
+enum SortField {
+    Id,
+    Name,
+    Price,
+    Quantity,
+    UpdatedAt,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SearchParams {
+    text: Option<String>,
+    category: Option<String>,
+    tags: Vec<String>,
+    in_stock_only: bool,
+    include_inactive: Option<bool>,
+    sort: SortField,
+    ascending: bool,
+    offset: u64,
+    limit: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct InventoryFilter {
+    text: Option<String>,
+    category: Option<String>,
+    tags: Vec<String>,
+    in_stock_only: bool,
+    include_inactive: bool,
+}
+
+struct FilterCriteria {
+    text_lower: Option<String>,
+    category: Option<String>,
+    tags: Vec<String>,
+    in_stock_only: bool,
+    include_inactive: bool,
+}
+
+impl FilterCriteria {
+    fn new(filter: &InventoryFilter) -> ApiResult<Self> {
+        Ok(FilterCriteria {
+            text_lower: filter.text.as_deref().map(str::to_lowercase),
+            category: filter.category.as_deref().map(normalize_category).transpose()?,
+            tags: filter
+                .tags
+                .iter()
+                .map(|tag| normalize_tag(tag))
+                .collect::<Result<Vec<_>, _>>()?,
+            in_stock_only: filter.in_stock_only,
+            include_inactive: filter.include_inactive,
+        })
+    }
+
+    fn matches(&self, item: &InventoryItem) -> bool {
+        self.text_lower.as_deref().is_none_or(|text| matches_text(item, text))
+            && self.category.as_ref().is_none_or(|category| {
+                item.category
+                    .as_ref()
+                    .is_some_and(|item_category| item_category.eq_ignore_ascii_case(category))
+            })
+            && self.tags.iter().all(|tag| item.tags.contains(tag))
+            && (!self.in_stock_only || item.quantity > 0)
+            && (item.active || self.include_inactive)
+    }
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SearchPage {
+    items: Vec<InventoryItem>,
+    total: u64,
+    offset: u64,
+    limit: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, Default)]
+struct ItemPatch {
+    name: Option<String>,
+    quantity: Option<u64>,
+    price: Option<f64>,
+    sku: Option<String>,
+    description: Option<String>,
+    category: Option<String>,
+    cost_price: Option<f64>,
+    reorder_point: Option<u64>,
+    target_stock: Option<u64>,
+}
+
+impl ItemPatch {
+    fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.quantity.is_none()
+            && self.price.is_none()
+            && self.sku.is_none()
+            && self.description.is_none()
+            && self.category.is_none()
+            && self.cost_price.is_none()
+            && self.reorder_point.is_none()
+            && self.target_stock.is_none()
+    }
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct PriceChangeOutcome {
+    id: ItemId,
+    old_price: Option<f64>,
+    new_price: f64,
+    applied_price: Option<f64>,
+    rejection: Option<BusinessError>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct PriceListReport {
+    dry_run: bool,
+    outcomes: Vec<PriceChangeOutcome>,
+    applied: u64,
+    rejected: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SalesExportPage {
+    json: String,
+    count: u64,
+    next_cursor: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct TargetProgress {
+    target: f64,
+    achieved: f64,
+    percent: f64,
+    projected_month_end: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug)]
+enum Granularity {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+enum AnomalyReason {
+    HighTotal { z_score: f64 },
+    DeepDiscount { discount_percent: f64 },
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SaleAnomaly {
+    sale: SaleRecord,
+    reasons: Vec<AnomalyReason>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum MethodKind {
+    Query,
+    Update,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum MethodAccess {
+    Read,
+    Authenticated,
+    Controller,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct MethodDoc {
+    name: String,
+    kind: MethodKind,
+    access: MethodAccess,
+    summary: String,
+    params: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct LedgerEntry {
+    sale_id: u64,
+    timestamp: u64,
+    item_id: ItemId,
+    item_name: String,
     quantity: u64,
+    unit_price: f64,
+    line_total: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SalesLedger {
+    entries: Vec<LedgerEntry>,
+    total_sales: f64,
+    total_tax: f64,
+    total_discount: f64,
+    count: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct TaxLiability {
+    taxable_sales: f64,
+    tax_collected: f64,
+    exempt_sales: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct EmployeeProductivity {
+    principal: Principal,
+    hours_with_sales: u64,
+    total_revenue: f64,
+    revenue_per_active_hour: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct EndOfDayReport {
+    day_start: u64,
+    day_end: u64,
+    num_sales: u64,
+    units_sold: u64,
+    revenue: f64,
+    tax_collected: f64,
+    profit: f64,
+    top_sellers: Vec<(String, u64)>,
+    returns: Vec<ReturnRecord>,
+    refunded: f64,
+    low_stock: Vec<ReorderSuggestion>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, Default)]
+struct HourStat {
+    revenue: f64,
+    num_sales: u64,
+    units: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ArchivedMonth {
+    month_start: u64,
+    num_sales: u64,
+    units_sold: u64,
+    revenue: f64,
+    profit: f64,
+    tax_collected: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct DayRevenue {
+    day: u32,
+    revenue: f64,
+    num_sales: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct HealthScore {
+    score: f64,
+    in_stock_percent: f64,
+    below_reorder_percent: f64,
+    dead_stock_percent: f64,
+    avg_days_of_cover: Option<f64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ScheduledReorder {
+    item: InventoryItem,
+    order_by: u64,
+    stockout_at: u64,
+    suggested_quantity: u64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ClearanceSuggestion {
+    item: InventoryItem,
+    days_since_last_sale: u64,
+    days_of_cover: Option<f64>,
+    markdown_percent: f64,
+    suggested_price: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct MarkdownSuggestion {
+    item_id: ItemId,
+    name: String,
+    current_price: f64,
+    suggested_price: f64,
+    markdown_percent: f64,
+    days_of_cover: Option<f64>,
+    projected_days_of_cover: Option<f64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct AgingBucket {
+    min_days: u64,
+    max_days: Option<u64>,
+    item_count: u64,
+    units: u64,
+    value: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct UnitEconomics {
     price: f64,
+    cost_price: f64,
+    unit_margin: f64,
+    margin_percent: f64,
+    markup_percent: f64,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct PriceSimulation {
+    item_id: ItemId,
+    cost_price: f64,
+    current_price: f64,
+    simulated_price: f64,
+    current_unit_margin: f64,
+    simulated_unit_margin: f64,
+    current_margin_percent: f64,
+    simulated_margin_percent: f64,
+    daily_units: f64,
+    projected_revenue_change: Option<f64>,
+    projected_profit_change: Option<f64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum TrendDirection {
+    Rising,
+    Falling,
+    Flat,
+    NoData,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct Trend {
+    direction: TrendDirection,
+    current_units: u64,
+    prior_units: u64,
+    percent_change: Option<f64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct RestockCostEstimate {
+    total_cost: f64,
+    uncosted_items: Vec<ItemId>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ReorderSuggestion {
+    item: InventoryItem,
+    to_order: u64,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
 struct SaleRecord {
+    seq: u64,
     timestamp: u64,
     items: Vec<SaleItem>,
     total_amount: f64,
+    #[serde(default)]
+    profit: f64,
+    #[serde(default)]
+    tax_amount: f64,
+    #[serde(default)]
+    deposit_total: f64,
+    foreign_total: Option<ForeignTotal>,
+    #[serde(default)]
+    note: Option<String>,
+    #[serde(default)]
+    cashier: Option<Principal>,
+    #[serde(default)]
+    status: SaleStatus,
+    #[serde(default)]
+    voided: Option<SaleVoid>,
+    #[serde(default)]
+    channel: Channel,
+    #[serde(default)]
+    skipped: Vec<(ItemId, u64)>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+enum Channel {
+    #[default]
+    InStore,
+    Online,
+    Marketplace,
+    Other(String),
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+enum SaleStatus {
+    #[default]
+    Completed,
+    Pending,
+    Cancelled,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SaleVoid {
+    timestamp: u64,
+    reason: String,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ForeignTotal {
+    code: String,
+    rate_to_base: f64,
+    amount: f64,
 }
 
 #[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
@@ -31,189 +527,7883 @@ struct SaleItem {
     name: String,
     quantity: u64,
     unit_price: f64,
+    list_price: f64,
+    unit_cost: Option<f64>,
+    category: Option<String>,
+    #[serde(default)]
+    tax_exempt: bool,
+    #[serde(default)]
+    serials: Vec<String>,
+    #[serde(default)]
+    deposit: f64,
+    #[serde(default)]
+    deposits_refunded: u64,
+    #[serde(default)]
+    discount_reason: Option<DiscountReason>,
 }
 
-thread_local! {
-    static INVENTORY: Memory<InventoryItem> = RefCell::new(HashMap::new());
-    static SALES: RefCell<Vec<SaleRecord>> = RefCell::new(Vec::new());
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum SerialStatus {
+    InStock,
+    Sold,
 }
 
-static NEXT_ITEM_ID: AtomicU64 = AtomicU64::new(1);
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SerialUnit {
+    serial: String,
+    status: SerialStatus,
+    sale_seq: Option<u64>,
+}
 
-fn generate_id() -> ItemId {
-    NEXT_ITEM_ID.fetch_add(1, Ordering::Relaxed)
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ItemAvailability {
+    item: InventoryItem,
+    reserved: u64,
+    available: u64,
+    online_available: u64,
 }
 
-#[update]
-fn add_item(name: String, quantity: u64, price: f64) -> Result<ItemId, String> {
-    if name.trim().is_empty() {
-        return Err("Item name cannot be empty.".to_string());
-    }
-    if quantity == 0 {
-        return Err("Quantity must be greater than zero.".to_string());
-    }
-    if price <= 0.0 {
-        return Err("Price must be positive.".to_string());
-    }
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct LineAvailability {
+    id: ItemId,
+    requested: u64,
+    available: u64,
+    sufficient: bool,
+}
 
-    let id = generate_id();
-    INVENTORY.with(|inventory| {
-        inventory.borrow_mut().insert(
-            id,
-            InventoryItem {
-                id,
-                name,
-                quantity,
-                price,
-            },
-        );
-    });
-    Ok(id)
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SaleLine {
+    id: ItemId,
+    quantity: u64,
+    override_price: Option<f64>,
+    tax_exempt: Option<bool>,
+    override_safety_stock: Option<bool>,
+    serials: Option<Vec<String>>,
+    discount_reason: Option<DiscountReason>,
 }
 
-#[update]
-fn update_item(id: ItemId, name: Option<String>, quantity: Option<u64>, price: Option<f64>) -> Result<(), String> {
-    INVENTORY.with(|inventory| {
-        let mut inventory = inventory.borrow_mut();
-        if let Some(item) = inventory.get_mut(&id) {
-            if let Some(new_name) = name {
-                if new_name.trim().is_empty() {
-                    return Err("Updated name cannot be empty.".to_string());
-                }
-                item.name = new_name;
-            }
-            if let Some(new_quantity) = quantity {
-                if new_quantity == 0 {
-                    return Err("Updated quantity must be greater than zero.".to_string());
-                }
-                item.quantity = new_quantity;
-            }
-            if let Some(new_price) = price {
-                if new_price <= 0.0 {
-                    return Err("Updated price must be positive.".to_string());
-                }
-                item.price = new_price;
-            }
-            Ok(())
-        } else {
-            Err(format!("Item with ID {} not found.", id))
+impl From<(ItemId, u64)> for SaleLine {
+    fn from((id, quantity): (ItemId, u64)) -> Self {
+        SaleLine {
+            id,
+            quantity,
+            override_price: None,
+            tax_exempt: None,
+            override_safety_stock: None,
+            serials: None,
+            discount_reason: None,
         }
-    })
+    }
 }
 
-#[update]
-fn remove_item(id: ItemId) -> Result<(), String> {
-    INVENTORY.with(|inventory| {
-        if inventory.borrow_mut().remove(&id).is_some() {
-            Ok(())
-        } else {
-            Err(format!("Item with ID {} not found.", id))
-        }
-    })
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum DiscountReason {
+    Promotion,
+    Loyalty,
+    PriceMatch,
+    Damaged,
+    Manager(String),
 }
 
-#[update]
-fn record_sale(items: Vec<(ItemId, u64)>) -> Result<SaleRecord, String> {
-    INVENTORY.with(|inventory| {
-        let mut inventory = inventory.borrow_mut();
-        let mut sale_items = Vec::new();
-        let mut total_amount = 0.0;
-
-        for (item_id, quantity) in items {
-            if let Some(item) = inventory.get_mut(&item_id) {
-                if item.quantity >= quantity {
-                    item.quantity -= quantity;
-                    sale_items.push(SaleItem {
-                        id: item.id,
-                        name: item.name.clone(),
-                        quantity,
-                        unit_price: item.price,
-                    });
-                    total_amount += item.price * quantity as f64;
-                } else {
-                    return Err(format!("Insufficient stock for item: {}", item.name));
-                }
-            } else {
-                return Err(format!("Item with ID {} not found", item_id));
-            }
-        }
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug, PartialEq)]
+enum ReturnReason {
+    Defective,
+    WrongItem,
+    CustomerChangedMind,
+    Other(String),
+}
 
-        let sale_record = SaleRecord {
-            timestamp: time(),
-            items: sale_items,
-            total_amount,
-        };
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ReturnRecord {
+    id: u64,
+    sale_seq: u64,
+    timestamp: u64,
+    items: Vec<SaleItem>,
+    refund_amount: f64,
+    reason: ReturnReason,
+}
 
-        SALES.with(|sales| sales.borrow_mut().push(sale_record.clone()));
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ItemSalesReport {
+    id: ItemId,
+    units_sold: u64,
+    revenue: f64,
+    num_sales: u64,
+    avg_units_per_sale: f64,
+    first_sold: Option<u64>,
+    last_sold: Option<u64>,
+}
 
-        Ok(sale_record)
-    })
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ItemWindowStats {
+    id: ItemId,
+    name: Option<String>,
+    units_sold: u64,
+    revenue: f64,
+    profit: f64,
 }
 
-#[query]
-fn get_inventory() -> Vec<InventoryItem> {
-    INVENTORY.with(|inventory| inventory.borrow().values().cloned().collect())
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ItemComparison {
+    a: ItemWindowStats,
+    b: ItemWindowStats,
 }
 
-#[query]
-fn get_item_details(id: ItemId) -> Option<InventoryItem> {
-    INVENTORY.with(|inventory| inventory.borrow().get(&id).cloned())
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct Profitability {
+    revenue: f64,
+    cost_of_goods: f64,
+    gross_profit: f64,
+    gross_margin_percent: f64,
+    uncosted_revenue: f64,
 }
 
-#[query]
-fn search_item_by_name(name: String) -> Vec<InventoryItem> {
-    let name_lower = name.to_lowercase();
-    INVENTORY.with(|inventory| {
-        inventory
-            .borrow()
-            .values()
-            .filter(|item| item.name.to_lowercase().contains(&name_lower))
-            .cloned()
-            .collect()
-    })
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct Reservation {
+    id: u64,
+    items: Vec<(ItemId, u64)>,
+    created_at: u64,
+    #[serde(default)]
+    sale_seq: Option<u64>,
 }
 
-#[query]
-fn get_sales() -> Vec<SaleRecord> {
-    SALES.with(|sales| sales.borrow().clone())
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum MovementKind {
+    Initial,
+    Sale,
+    Restock,
+    Return,
+    Adjustment,
+    Transfer,
+    // Damaged or lost stock; nothing records write-offs yet.
+    WriteOff,
+    Void,
 }
 
-#[query]
-fn financial_overview() -> (f64, f64) {
-    let total_sales: f64 = SALES.with(|sales| sales.borrow().iter().map(|sale| sale.total_amount).sum());
-    let inventory_value: f64 = INVENTORY.with(|inventory| {
-        inventory.borrow().values().map(|item| item.quantity as f64 * item.price).sum()
-    });
-    (total_sales, inventory_value)
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct StockMovement {
+    item_id: ItemId,
+    timestamp: u64,
+    kind: MovementKind,
+    delta: i64,
+    ref_id: Option<u64>,
 }
 
-#[query]
-fn reorder_suggestions(threshold: u64) -> Vec<InventoryItem> {
-    INVENTORY.with(|inventory| {
-        inventory
-            .borrow()
-            .values()
-            .filter(|item| item.quantity < threshold)
-            .cloned()
-            .collect()
-    })
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct StockLedgerEntry {
+    timestamp: u64,
+    kind: MovementKind,
+    delta: i64,
+    balance_after: u64,
+    ref_id: Option<u64>,
 }
 
-#[query]
-fn get_top_selling_items(n: usize) -> Vec<(String, u64)> {
-    let mut sales_count = HashMap::new();
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum StockAlertKind {
+    LowStock,
+    BackInStock,
+}
 
-    SALES.with(|sales| {
-        for sale in sales.borrow().iter() {
-            for item in &sale.items {
-                *sales_count.entry(item.name.clone()).or_insert(0) += item.quantity;
-            }
-        }
-    });
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct StockAlert {
+    item_id: ItemId,
+    name: String,
+    kind: StockAlertKind,
+    quantity: u64,
+    reorder_point: u64,
+    timestamp: u64,
+}
 
-    let mut sales_vec: Vec<_> = sales_count.into_iter().collect();
-    sales_vec.sort_by(|a, b| b.1.cmp(&a.1));
-    sales_vec.into_iter().take(n).collect()
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct ReconcileReport {
+    id: ItemId,
+    recorded_quantity: u64,
+    expected_quantity: i64,
+    discrepancy: i64,
+    consistent: bool,
 }
 
-ic_cdk::export_candid!();
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct CurrencyConfig {
+    code: String,
+    decimal_places: u8,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum BarcodeFormat {
+    Ean13,
+    UpcA,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+enum MarginPolicy {
+    #[default]
+    Reject,
+    Cap,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+enum ValuationMethod {
+    LastCost,
+    WeightedAverage,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct Config {
+    max_sale_lines: u64,
+    utc_offset_minutes: i32,
+    day_cutoff_hour: u8,
+    reservation_ttl_ns: u64,
+    pending_sale_window_ns: u64,
+    sale_amend_window_ns: u64,
+    sales_retention_ns: Option<u64>,
+    snapshot_interval_ns: Option<u64>,
+    maintenance_interval_ns: Option<u64>,
+    default_reorder_point: u64,
+    category_reorder_thresholds: BTreeMap<String, u64>,
+    currency: CurrencyConfig,
+    exchange_rates: BTreeMap<String, f64>,
+    tax_rate_percent: f64,
+    markdown_min_margin_percent: f64,
+    min_margin_percent: Option<f64>,
+    margin_policy: MarginPolicy,
+    max_price: f64,
+    max_quantity_per_item: u64,
+    valuation_method: ValuationMethod,
+    barcode_format: Option<BarcodeFormat>,
+    rate_limit_capacity: u64,
+    rate_limit_refill_per_minute: u64,
+    monthly_target: f64,
+    min_sale_amount: f64,
+    allow_public_read: bool,
+    allow_zero_initial_stock: bool,
+    featured_items: Vec<ItemId>,
+    owner: Option<Principal>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_sale_lines: DEFAULT_MAX_SALE_LINES,
+            utc_offset_minutes: 0,
+            day_cutoff_hour: 0,
+            reservation_ttl_ns: DEFAULT_RESERVATION_TTL_NS,
+            pending_sale_window_ns: DEFAULT_PENDING_SALE_WINDOW_NS,
+            sale_amend_window_ns: DEFAULT_SALE_AMEND_WINDOW_NS,
+            sales_retention_ns: None,
+            snapshot_interval_ns: None,
+            maintenance_interval_ns: Some(DEFAULT_MAINTENANCE_INTERVAL_NS),
+            default_reorder_point: 0,
+            category_reorder_thresholds: BTreeMap::new(),
+            currency: CurrencyConfig {
+                code: "USD".to_string(),
+                decimal_places: 2,
+            },
+            exchange_rates: BTreeMap::new(),
+            tax_rate_percent: 0.0,
+            markdown_min_margin_percent: DEFAULT_MARKDOWN_MIN_MARGIN_PERCENT,
+            min_margin_percent: None,
+            margin_policy: MarginPolicy::Reject,
+            max_price: DEFAULT_MAX_PRICE,
+            max_quantity_per_item: DEFAULT_MAX_QUANTITY_PER_ITEM,
+            valuation_method: ValuationMethod::LastCost,
+            barcode_format: None,
+            rate_limit_capacity: DEFAULT_RATE_LIMIT_CAPACITY,
+            rate_limit_refill_per_minute: DEFAULT_RATE_LIMIT_REFILL_PER_MINUTE,
+            monthly_target: 0.0,
+            min_sale_amount: 0.0,
+            allow_public_read: true,
+            allow_zero_initial_stock: false,
+            featured_items: Vec::new(),
+            owner: None,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct InitArgs {
+    owner: Option<Principal>,
+    currency: Option<CurrencyConfig>,
+    tax_rate_percent: Option<f64>,
+}
+
+#[derive(CandidType, Deserialize, Serialize, Clone, Debug)]
+struct SnapshotInfo {
+    id: u64,
+    timestamp: u64,
+    size_bytes: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+struct StableState {
+    inventory: BTreeMap<ItemId, InventoryItem>,
+    #[serde(default)]
+    sales: Vec<SaleRecord>,
+    returns: Vec<ReturnRecord>,
+    reservations: BTreeMap<u64, Reservation>,
+    movements: Vec<StockMovement>,
+    config: Config,
+    #[serde(default)]
+    client_keys: HashMap<String, ItemId>,
+    #[serde(default)]
+    serials: BTreeMap<ItemId, Vec<SerialUnit>>,
+    #[serde(default)]
+    stock_alerts: Vec<StockAlert>,
+    #[serde(default)]
+    archived_months: BTreeMap<u64, ArchivedMonth>,
+    #[serde(default)]
+    snapshots: Vec<SnapshotInfo>,
+    next_item_id: ItemId,
+    next_sale_seq: u64,
+    next_return_id: u64,
+    next_reservation_id: u64,
+    #[serde(default)]
+    catalog_version: u64,
+    #[serde(default)]
+    deal_seed: Option<u64>,
+    #[serde(default)]
+    daily_deal: Option<(i64, Option<ItemId>)>,
+    #[serde(default)]
+    completed_sales: Option<u64>,
+}
+
+const UPGRADE_MEMORY_ID: MemoryId = MemoryId::new(0);
+const SALES_INDEX_MEMORY_ID: MemoryId = MemoryId::new(1);
+const SALES_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2);
+const SALES_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(3);
+const FIRST_SNAPSHOT_MEMORY_ID: u8 = 4;
+const SNAPSHOT_SLOTS: u64 = 3;
+
+impl Storable for SaleRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).expect("Failed to encode sale record."))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).expect("Failed to decode sale record.")
+    }
+}
+
+fn stable_memory(id: MemoryId) -> StableMemory {
+    MEMORY_MANAGER.with(|manager| manager.borrow().get(id))
+}
+
+// Sales are appended to a stable log and indexed by seq; rewriting a sale appends a new version
+// and repoints the index, so records of any size live in stable memory without per-entry bounds.
+struct SaleStore {
+    index: StableBTreeMap<u64, u64, StableMemory>,
+    log: StableLog<SaleRecord, StableMemory, StableMemory>,
+}
+
+impl SaleStore {
+    fn init() -> Self {
+        SaleStore {
+            index: StableBTreeMap::init(stable_memory(SALES_INDEX_MEMORY_ID)),
+            log: StableLog::init(
+                stable_memory(SALES_LOG_INDEX_MEMORY_ID),
+                stable_memory(SALES_LOG_DATA_MEMORY_ID),
+            )
+            .expect("Failed to initialize the sales log."),
+        }
+    }
+
+    // Keeps COMPLETED_SALES in step as sales enter or leave the completed set.
+    fn insert(&mut self, sale: &SaleRecord) {
+        let was_counted = self.get(sale.seq).is_some_and(|old| is_counted(&old));
+        adjust_completed_sales(was_counted, is_counted(sale));
+        let position = self.log.append(sale).expect("Failed to append to the sales log.");
+        self.index.insert(sale.seq, position);
+    }
+
+    fn get(&self, seq: u64) -> Option<SaleRecord> {
+        self.index.get(&seq).and_then(|position| self.log.get(position))
+    }
+
+    // Drops the sale from the index; its log entries stay behind but are no longer reachable.
+    fn remove(&mut self, seq: u64) {
+        if self.get(seq).is_some_and(|old| is_counted(&old)) {
+            COMPLETED_SALES.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.index.remove(&seq);
+    }
+
+    fn iter_all(&self) -> impl Iterator<Item = SaleRecord> + '_ {
+        self.index.iter().filter_map(|(_, position)| self.log.get(position))
+    }
+
+    // Only completed sales count toward aggregates; pending, cancelled and voided ones stay reachable by seq.
+    fn iter(&self) -> impl Iterator<Item = SaleRecord> + '_ {
+        self.iter_all().filter(is_counted)
+    }
+
+    fn after(&self, seq: Option<u64>) -> impl Iterator<Item = SaleRecord> + '_ {
+        let lower = seq.map_or(Bound::Unbounded, Bound::Excluded);
+        self.index
+            .range((lower, Bound::Unbounded))
+            .filter_map(|(_, position)| self.log.get(position))
+    }
+
+    fn latest(&self, limit: usize) -> Vec<SaleRecord> {
+        let mut latest = Vec::new();
+        let mut next = self.index.last_key_value();
+        while let Some((seq, position)) = next {
+            if latest.len() >= limit {
+                break;
+            }
+            latest.extend(self.log.get(position));
+            next = self.index.iter_upper_bound(&seq).next();
+        }
+        latest
+    }
+}
+
+fn is_counted(sale: &SaleRecord) -> bool {
+    sale.status == SaleStatus::Completed && sale.voided.is_none()
+}
+
+fn adjust_completed_sales(was_counted: bool, is_counted: bool) {
+    match (was_counted, is_counted) {
+        (false, true) => {
+            COMPLETED_SALES.fetch_add(1, Ordering::Relaxed);
+        }
+        (true, false) => {
+            COMPLETED_SALES.fetch_sub(1, Ordering::Relaxed);
+        }
+        _ => {}
+    }
+}
+
+thread_local! {
+    static INVENTORY: Memory<InventoryItem> = const { RefCell::new(BTreeMap::new()) };
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static SALES: RefCell<SaleStore> = RefCell::new(SaleStore::init());
+    static RETURNS: RefCell<Vec<ReturnRecord>> = const { RefCell::new(Vec::new()) };
+    static RESERVATIONS: RefCell<BTreeMap<u64, Reservation>> = const { RefCell::new(BTreeMap::new()) };
+    static MOVEMENTS: RefCell<Vec<StockMovement>> = const { RefCell::new(Vec::new()) };
+    static CONFIG: RefCell<Config> = RefCell::new(Config::default());
+    static CLIENT_KEYS: RefCell<HashMap<String, ItemId>> = RefCell::new(HashMap::new());
+    static SERIALS: RefCell<BTreeMap<ItemId, Vec<SerialUnit>>> = const { RefCell::new(BTreeMap::new()) };
+    static STOCK_ALERTS: RefCell<Vec<StockAlert>> = const { RefCell::new(Vec::new()) };
+    static ARCHIVED_MONTHS: RefCell<BTreeMap<u64, ArchivedMonth>> = const { RefCell::new(BTreeMap::new()) };
+    static SNAPSHOTS: RefCell<Vec<SnapshotInfo>> = const { RefCell::new(Vec::new()) };
+    static LAST_MAINTENANCE: Cell<u64> = const { Cell::new(0) };
+    static DEAL_SEED: Cell<Option<u64>> = const { Cell::new(None) };
+    static DEAL_SEED_REQUESTED: Cell<bool> = const { Cell::new(false) };
+    static DAILY_DEAL: Cell<Option<(i64, Option<ItemId>)>> = const { Cell::new(None) };
+    static RATE_BUCKETS: RefCell<HashMap<Principal, (f64, u64)>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_ITEM_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_SALE_SEQ: AtomicU64 = AtomicU64::new(1);
+static NEXT_RETURN_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_RESERVATION_ID: AtomicU64 = AtomicU64::new(1);
+static CATALOG_VERSION: AtomicU64 = AtomicU64::new(0);
+static COMPLETED_SALES: AtomicU64 = AtomicU64::new(0);
+
+const MAX_NAME_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
+const MAX_CLIENT_KEY_LENGTH: usize = 128;
+const MAX_SALE_NOTE_LENGTH: usize = 500;
+const MAX_CHANNEL_LENGTH: usize = 64;
+const MAX_SERIAL_LENGTH: usize = 128;
+const MAX_STOCK_ALERTS: usize = 1000;
+const MAX_ARCHIVE_BATCH: usize = 1000;
+const UNCATEGORIZED: &str = "Uncategorized";
+const AGING_BUCKET_BOUNDS_DAYS: [u64; 3] = [30, 60, 90];
+const DEAD_STOCK_DAYS: u64 = 90;
+const DEAL_MAX_WEIGHT_FACTOR: f64 = 10.0;
+const TREND_FLAT_PERCENT: f64 = 5.0;
+const COVER_WINDOW_DAYS: u64 = 30;
+const TARGET_DAYS_OF_COVER: f64 = 30.0;
+const CLEARANCE_MIN_IDLE_DAYS: u64 = 60;
+const CLEARANCE_OVERSTOCK_DAYS_OF_COVER: f64 = 90.0;
+const CLEARANCE_MARKDOWN_PER_MONTH_IDLE: f64 = 10.0;
+const CLEARANCE_MAX_MARKDOWN_PERCENT: f64 = 50.0;
+const DEFAULT_MARKDOWN_MIN_MARGIN_PERCENT: f64 = 10.0;
+const MARKDOWN_PERCENT_PER_OVERSTOCK_MULTIPLE: f64 = 10.0;
+const OVERSTOCK_MAX_MARKDOWN_PERCENT: f64 = 50.0;
+// Assumed demand lift per percent of markdown, e.g. 2.0 means a 10% markdown sells 20% faster.
+const MARKDOWN_DEMAND_ELASTICITY: f64 = 2.0;
+const HEALTH_IN_STOCK_WEIGHT: f64 = 0.35;
+const HEALTH_REORDER_WEIGHT: f64 = 0.25;
+const HEALTH_DEAD_STOCK_WEIGHT: f64 = 0.25;
+const HEALTH_COVER_WEIGHT: f64 = 0.15;
+const MAX_CURRENCY_DECIMAL_PLACES: u8 = 8;
+const MAX_TAGS_PER_ITEM: usize = 20;
+const MAX_FEATURED_ITEMS: usize = 50;
+const MAX_PAGE_SIZE: u64 = 100;
+const MAX_TOP_N: usize = 1000;
+const MAX_EXPORT_PAGE_SIZE: u64 = 500;
+const MAX_LEDGER_ENTRIES: usize = 5000;
+const MAX_SERIES_BUCKETS: i64 = 1000;
+const END_OF_DAY_TOP_SELLERS: usize = 5;
+const ANOMALY_Z_SCORE: f64 = 3.0;
+const ANOMALY_MIN_BASELINE_SALES: usize = 3;
+const ANOMALY_DISCOUNT_PERCENT: f64 = 50.0;
+const SEARCH_TEXT_WEIGHT: f64 = 0.6;
+const SEARCH_POPULARITY_WEIGHT: f64 = 0.4;
+const SEARCH_POPULARITY_WINDOW_DAYS: u64 = 30;
+const DEFAULT_MAX_SALE_LINES: u64 = 500;
+const DEFAULT_RATE_LIMIT_CAPACITY: u64 = 60;
+const DEFAULT_RATE_LIMIT_REFILL_PER_MINUTE: u64 = 60;
+const DEFAULT_MAX_PRICE: f64 = 1_000_000_000.0;
+const DEFAULT_MAX_QUANTITY_PER_ITEM: u64 = 1_000_000_000_000;
+const NANOS_PER_MINUTE: i128 = 60_000_000_000;
+const NANOS_PER_HOUR: i128 = 60 * NANOS_PER_MINUTE;
+const NANOS_PER_DAY: i128 = 24 * NANOS_PER_HOUR;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const MAX_FUTURE_WINDOW_START_NS: u64 = 24 * 60 * 60 * NANOS_PER_SECOND;
+const MAX_MAINTENANCE_INTERVAL_SECS: u64 = 7 * 24 * 60 * 60;
+const DEFAULT_RESERVATION_TTL_NS: u64 = 30 * 60 * NANOS_PER_SECOND;
+const DEFAULT_PENDING_SALE_WINDOW_NS: u64 = 15 * 60 * NANOS_PER_SECOND;
+const DEFAULT_SALE_AMEND_WINDOW_NS: u64 = 5 * 60 * NANOS_PER_SECOND;
+const DEFAULT_MAINTENANCE_INTERVAL_NS: u64 = 60 * 60 * NANOS_PER_SECOND;
+
+fn generate_id() -> ItemId {
+    NEXT_ITEM_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn next_sale_seq() -> u64 {
+    NEXT_SALE_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+fn next_return_id() -> u64 {
+    NEXT_RETURN_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn next_reservation_id() -> u64 {
+    NEXT_RESERVATION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+fn bump_catalog_version() {
+    CATALOG_VERSION.fetch_add(1, Ordering::Relaxed);
+}
+
+fn is_owner(principal: &Principal) -> bool {
+    is_controller(principal) || CONFIG.with(|config| config.borrow().owner == Some(*principal))
+}
+
+fn caller_is_authenticated() -> Result<(), String> {
+    if caller() == Principal::anonymous() {
+        return Err("Anonymous callers cannot modify canister state.".to_string());
+    }
+    Ok(())
+}
+
+// Called first in every update rather than from the guard, so a throttled caller gets a typed `RateLimited` error.
+fn check_rate_limit() -> ApiResult<()> {
+    let principal = caller();
+    if is_owner(&principal) {
+        return Ok(());
+    }
+    take_rate_limit_token(principal, time())
+}
+
+fn take_rate_limit_token(principal: Principal, now: u64) -> ApiResult<()> {
+    let (capacity, refill_per_minute) =
+        CONFIG.with(|config| (config.borrow().rate_limit_capacity, config.borrow().rate_limit_refill_per_minute));
+    let refill_per_ns = refill_per_minute as f64 / (60 * NANOS_PER_SECOND) as f64;
+
+    RATE_BUCKETS.with(|buckets| {
+        let mut buckets = buckets.borrow_mut();
+        let (tokens, last) = buckets.entry(principal).or_insert((capacity as f64, now));
+        *tokens = (*tokens + now.saturating_sub(*last) as f64 * refill_per_ns).min(capacity as f64);
+        *last = now;
+        if *tokens < 1.0 {
+            let retry_after_secs = ((1.0 - *tokens) / refill_per_ns / NANOS_PER_SECOND as f64).ceil() as u64;
+            return Err(BusinessError::RateLimited {
+                retry_after: retry_after_secs,
+            });
+        }
+        *tokens -= 1.0;
+        Ok(())
+    })
+}
+
+fn prune_rate_buckets(now: u64) {
+    let (capacity, refill_per_minute) =
+        CONFIG.with(|config| (config.borrow().rate_limit_capacity, config.borrow().rate_limit_refill_per_minute));
+    let refill_per_ns = refill_per_minute as f64 / (60 * NANOS_PER_SECOND) as f64;
+    RATE_BUCKETS.with(|buckets| {
+        buckets.borrow_mut().retain(|_, (tokens, last)| {
+            *tokens + now.saturating_sub(*last) as f64 * refill_per_ns < capacity as f64
+        })
+    });
+}
+
+fn caller_can_read() -> Result<(), String> {
+    if caller() == Principal::anonymous() && !CONFIG.with(|config| config.borrow().allow_public_read) {
+        return Err("Anonymous reads are disabled for this canister.".to_string());
+    }
+    Ok(())
+}
+
+fn record_movement(item: &InventoryItem, kind: MovementKind, old_quantity: u64, new_quantity: u64, ref_id: Option<u64>) {
+    let delta = (new_quantity as i128 - old_quantity as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    if delta == 0 && kind != MovementKind::Initial {
+        return;
+    }
+    bump_catalog_version();
+    MOVEMENTS.with(|movements| {
+        movements.borrow_mut().push(StockMovement {
+            item_id: item.id,
+            timestamp: time(),
+            kind,
+            delta,
+            ref_id,
+        })
+    });
+    if kind != MovementKind::Initial && kind != MovementKind::Transfer {
+        record_stock_alert(item, old_quantity, new_quantity);
+    }
+}
+
+// Alerts fire only when stock crosses the reorder point, not on every change while already low.
+fn record_stock_alert(item: &InventoryItem, old_quantity: u64, new_quantity: u64) {
+    let default_reorder_point = CONFIG.with(|config| config.borrow().default_reorder_point);
+    let reorder_point = effective_reorder_point(item, default_reorder_point);
+    let kind = if old_quantity >= reorder_point && new_quantity < reorder_point {
+        StockAlertKind::LowStock
+    } else if old_quantity < reorder_point && new_quantity >= reorder_point {
+        StockAlertKind::BackInStock
+    } else {
+        return;
+    };
+    STOCK_ALERTS.with(|alerts| {
+        let mut alerts = alerts.borrow_mut();
+        if alerts.len() >= MAX_STOCK_ALERTS {
+            alerts.remove(0);
+        }
+        alerts.push(StockAlert {
+            item_id: item.id,
+            name: item.name.clone(),
+            kind,
+            quantity: new_quantity,
+            reorder_point,
+            timestamp: time(),
+        });
+    });
+}
+
+#[query(guard = "caller_can_read")]
+fn get_stock_alerts(since: u64) -> Vec<StockAlert> {
+    STOCK_ALERTS.with(|alerts| {
+        alerts
+            .borrow()
+            .iter()
+            .filter(|alert| alert.timestamp >= since)
+            .cloned()
+            .collect()
+    })
+}
+
+// The owner defaults to the deployer; a bad argument traps so the install never serves calls half-configured.
+#[init]
+fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+    let owner = args.owner.unwrap_or_else(caller);
+    apply_init_args(InitArgs {
+        owner: Some(owner),
+        ..args
+    })
+    .unwrap_or_else(|err| ic_cdk::trap(&err.to_string()));
+}
+
+fn apply_init_args(args: InitArgs) -> ApiResult<()> {
+    if let Some(currency) = args.currency {
+        apply_currency(currency.code, currency.decimal_places)?;
+    }
+    if let Some(percent) = args.tax_rate_percent {
+        apply_tax_rate(percent)?;
+    }
+    if let Some(owner) = args.owner {
+        if owner == Principal::anonymous() {
+            return Err(BusinessError::InvalidInput(
+                "The owner cannot be the anonymous principal.".to_string(),
+            ));
+        }
+        CONFIG.with(|config| config.borrow_mut().owner = Some(owner));
+    }
+    Ok(())
+}
+
+#[pre_upgrade]
+fn pre_upgrade() {
+    write_state(UPGRADE_MEMORY_ID, &capture_state(Vec::new()));
+}
+
+fn capture_state(sales: Vec<SaleRecord>) -> StableState {
+    StableState {
+        inventory: INVENTORY.with(|inventory| inventory.borrow().clone()),
+        sales,
+        returns: RETURNS.with(|returns| returns.borrow().clone()),
+        reservations: RESERVATIONS.with(|reservations| reservations.borrow().clone()),
+        movements: MOVEMENTS.with(|movements| movements.borrow().clone()),
+        config: CONFIG.with(|config| config.borrow().clone()),
+        client_keys: CLIENT_KEYS.with(|client_keys| client_keys.borrow().clone()),
+        serials: SERIALS.with(|serials| serials.borrow().clone()),
+        stock_alerts: STOCK_ALERTS.with(|alerts| alerts.borrow().clone()),
+        archived_months: ARCHIVED_MONTHS.with(|months| months.borrow().clone()),
+        snapshots: SNAPSHOTS.with(|snapshots| snapshots.borrow().clone()),
+        next_item_id: NEXT_ITEM_ID.load(Ordering::Relaxed),
+        next_sale_seq: NEXT_SALE_SEQ.load(Ordering::Relaxed),
+        next_return_id: NEXT_RETURN_ID.load(Ordering::Relaxed),
+        next_reservation_id: NEXT_RESERVATION_ID.load(Ordering::Relaxed),
+        catalog_version: CATALOG_VERSION.load(Ordering::Relaxed),
+        deal_seed: DEAL_SEED.with(Cell::get),
+        daily_deal: DAILY_DEAL.with(Cell::get),
+        completed_sales: Some(COMPLETED_SALES.load(Ordering::Relaxed)),
+    }
+}
+
+fn write_state(memory_id: MemoryId, state: &StableState) -> u64 {
+    let bytes = Encode!(state).expect("Failed to encode state.");
+    let mut memory = stable_memory(memory_id);
+    let mut writer = Writer::new(&mut memory, 0);
+    writer
+        .write(&(bytes.len() as u64).to_le_bytes())
+        .and_then(|_| writer.write(&bytes))
+        .expect("Failed to save state to stable memory.");
+    bytes.len() as u64
+}
+
+// Releases before the stable sales log wrote the whole state, sales included, with stable_save.
+fn is_legacy_stable_layout() -> bool {
+    if ic_cdk::api::stable::stable64_size() == 0 {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    ic_cdk::api::stable::stable64_read(0, &mut magic);
+    &magic == b"DIDL"
+}
+
+fn read_state(memory_id: MemoryId) -> StableState {
+    let memory = stable_memory(memory_id);
+    let mut length = [0u8; 8];
+    memory.read(0, &mut length);
+    let mut bytes = vec![0u8; u64::from_le_bytes(length) as usize];
+    memory.read(8, &mut bytes);
+    Decode!(&bytes, StableState).expect("Failed to restore state from stable memory.")
+}
+
+// Upgrade arguments are optional; anything left unset keeps its restored value.
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    let state = if is_legacy_stable_layout() {
+        let (state,): (StableState,) =
+            ic_cdk::storage::stable_restore().expect("Failed to restore state from stable memory.");
+        state
+    } else {
+        read_state(UPGRADE_MEMORY_ID)
+    };
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        for sale in &state.sales {
+            sales.insert(sale);
+        }
+    });
+    restore_state(state);
+    if let Some(args) = args {
+        apply_init_args(args).unwrap_or_else(|err| ic_cdk::trap(&err.to_string()));
+    }
+}
+
+// Restores everything except sales, which live in their own stable structures.
+fn restore_state(state: StableState) {
+    INVENTORY.with(|inventory| *inventory.borrow_mut() = state.inventory);
+    RETURNS.with(|returns| *returns.borrow_mut() = state.returns);
+    RESERVATIONS.with(|reservations| *reservations.borrow_mut() = state.reservations);
+    MOVEMENTS.with(|movements| *movements.borrow_mut() = state.movements);
+    CONFIG.with(|config| *config.borrow_mut() = state.config);
+    CLIENT_KEYS.with(|client_keys| *client_keys.borrow_mut() = state.client_keys);
+    SERIALS.with(|serials| *serials.borrow_mut() = state.serials);
+    STOCK_ALERTS.with(|alerts| *alerts.borrow_mut() = state.stock_alerts);
+    ARCHIVED_MONTHS.with(|months| *months.borrow_mut() = state.archived_months);
+    NEXT_ITEM_ID.store(state.next_item_id, Ordering::Relaxed);
+    NEXT_SALE_SEQ.store(state.next_sale_seq, Ordering::Relaxed);
+    NEXT_RETURN_ID.store(state.next_return_id, Ordering::Relaxed);
+    NEXT_RESERVATION_ID.store(state.next_reservation_id, Ordering::Relaxed);
+    CATALOG_VERSION.store(state.catalog_version, Ordering::Relaxed);
+    DEAL_SEED.with(|seed| seed.set(state.deal_seed));
+    DAILY_DEAL.with(|deal| deal.set(state.daily_deal));
+    // State saved before the counter existed gets it from one pass over the restored sales.
+    let completed_sales = state
+        .completed_sales
+        .unwrap_or_else(|| SALES.with(|sales| sales.borrow().iter().count() as u64));
+    COMPLETED_SALES.store(completed_sales, Ordering::Relaxed);
+    SNAPSHOTS.with(|snapshots| *snapshots.borrow_mut() = state.snapshots);
+}
+
+fn normalize_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn validate_name_length(name: &str) -> ApiResult<()> {
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Item name cannot exceed {} characters.",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn normalize_sku(sku: &str) -> ApiResult<String> {
+    let sku = sku.trim();
+    if sku.is_empty() {
+        return Err(BusinessError::InvalidInput("SKU cannot be empty.".to_string()));
+    }
+    Ok(sku.to_string())
+}
+
+// When a barcode format is configured, SKUs on new and updated items must carry a valid check digit.
+fn check_barcode(sku: &str) -> ApiResult<()> {
+    let Some(format) = CONFIG.with(|config| config.borrow().barcode_format) else {
+        return Ok(());
+    };
+    let (name, length, odd_weight, even_weight) = match format {
+        BarcodeFormat::Ean13 => ("EAN-13", 13, 1, 3),
+        BarcodeFormat::UpcA => ("UPC-A", 12, 3, 1),
+    };
+    let digits: Vec<u32> = sku.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() != length || sku.chars().count() != length {
+        return Err(BusinessError::InvalidBarcode(format!(
+            "SKU {} is not a valid {} barcode: expected {} digits.",
+            sku, name, length
+        )));
+    }
+    let sum: u32 = digits[..length - 1]
+        .iter()
+        .enumerate()
+        .map(|(index, digit)| digit * if index % 2 == 0 { odd_weight } else { even_weight })
+        .sum();
+    if (10 - sum % 10) % 10 != digits[length - 1] {
+        return Err(BusinessError::InvalidBarcode(format!(
+            "SKU {} is not a valid {} barcode: check digit mismatch.",
+            sku, name
+        )));
+    }
+    Ok(())
+}
+
+fn normalize_description(description: &str) -> ApiResult<Option<String>> {
+    let description = description.trim();
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Description cannot exceed {} characters.",
+            MAX_DESCRIPTION_LENGTH
+        )));
+    }
+    Ok(Some(description.to_string()).filter(|description| !description.is_empty()))
+}
+
+fn normalize_variant(variant: &str) -> ApiResult<String> {
+    let variant = normalize_name(variant);
+    if variant.is_empty() {
+        return Err(BusinessError::InvalidInput("Variant cannot be empty.".to_string()));
+    }
+    if variant.chars().count() > MAX_NAME_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Variant cannot exceed {} characters.",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(variant)
+}
+
+fn normalize_category(category: &str) -> ApiResult<String> {
+    let category = normalize_name(category);
+    if category.is_empty() {
+        return Err(BusinessError::InvalidInput("Category cannot be empty.".to_string()));
+    }
+    Ok(category)
+}
+
+fn normalize_tag(tag: &str) -> ApiResult<String> {
+    let tag = normalize_name(tag).to_lowercase();
+    if tag.is_empty() {
+        return Err(BusinessError::InvalidInput("Tag cannot be empty.".to_string()));
+    }
+    Ok(tag)
+}
+
+fn find_item_by_variant(
+    inventory: &BTreeMap<ItemId, InventoryItem>,
+    name: &str,
+    variant: Option<&str>,
+) -> Option<ItemId> {
+    let name = name.to_lowercase();
+    let variant = variant.map(str::to_lowercase);
+    inventory
+        .values()
+        .find(|item| item.name.to_lowercase() == name && item.variant.as_deref().map(str::to_lowercase) == variant)
+        .map(|item| item.id)
+}
+
+fn describe_variant(name: &str, variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => format!("{} / {}", name, variant),
+        None => name.to_string(),
+    }
+}
+
+fn find_item_by_sku(inventory: &BTreeMap<ItemId, InventoryItem>, sku: &str) -> Option<ItemId> {
+    inventory
+        .values()
+        .find(|item| item.sku.as_deref() == Some(sku))
+        .map(|item| item.id)
+}
+
+#[query(guard = "caller_can_read")]
+fn get_config() -> Config {
+    CONFIG.with(|config| config.borrow().clone())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_max_sale_lines(max_sale_lines: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the sale line limit.".to_string(),
+        ));
+    }
+    if max_sale_lines == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Maximum sale lines must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().max_sale_lines = max_sale_lines);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_utc_offset(utc_offset_minutes: i32) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !(-12 * 60..=14 * 60).contains(&utc_offset_minutes) {
+        return Err(BusinessError::InvalidInput(
+            "UTC offset must be between -12:00 and +14:00.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().utc_offset_minutes = utc_offset_minutes);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_default_reorder_point(default_reorder_point: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    CONFIG.with(|config| config.borrow_mut().default_reorder_point = default_reorder_point);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_category_reorder_threshold(category: String, threshold: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    let category = normalize_category(&category)?;
+    CONFIG.with(|config| {
+        let thresholds = &mut config.borrow_mut().category_reorder_thresholds;
+        match threshold {
+            Some(threshold) => thresholds.insert(category, threshold),
+            None => thresholds.remove(&category),
+        }
+    });
+    Ok(())
+}
+
+fn effective_reorder_point(item: &InventoryItem, default_reorder_point: u64) -> u64 {
+    item.reorder_point.unwrap_or_else(|| {
+        CONFIG.with(|config| {
+            item.category
+                .as_ref()
+                .and_then(|category| config.borrow().category_reorder_thresholds.get(category).copied())
+                .unwrap_or(default_reorder_point)
+        })
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_currency(code: String, decimal_places: u8) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the currency.".to_string(),
+        ));
+    }
+    apply_currency(code, decimal_places)
+}
+
+fn apply_currency(code: String, decimal_places: u8) -> ApiResult<()> {
+    let code = normalize_currency_code(&code)?;
+    if decimal_places > MAX_CURRENCY_DECIMAL_PLACES {
+        return Err(BusinessError::InvalidInput(format!(
+            "Currency decimal places cannot exceed {}.",
+            MAX_CURRENCY_DECIMAL_PLACES
+        )));
+    }
+    if decimal_places == 0 {
+        let fractional = INVENTORY.with(|inventory| {
+            inventory
+                .borrow()
+                .values()
+                .find(|item| item.price.fract() != 0.0)
+                .map(|item| item.name.clone())
+        });
+        if let Some(name) = fractional {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} has a fractional price; reprice it before switching to {}.",
+                name, code
+            )));
+        }
+    }
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        config.exchange_rates.remove(&code);
+        config.currency = CurrencyConfig { code, decimal_places };
+    });
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_value_caps(max_price: f64, max_quantity_per_item: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change value caps.".to_string(),
+        ));
+    }
+    if !max_price.is_finite() || max_price <= 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Maximum price must be a positive, finite amount.".to_string(),
+        ));
+    }
+    if max_quantity_per_item == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Maximum quantity per item must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        config.max_price = max_price;
+        config.max_quantity_per_item = max_quantity_per_item;
+    });
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_rate_limit(capacity: u64, refill_per_minute: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the rate limit.".to_string(),
+        ));
+    }
+    if capacity == 0 || refill_per_minute == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Rate limit capacity and refill rate must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        config.rate_limit_capacity = capacity;
+        config.rate_limit_refill_per_minute = refill_per_minute;
+    });
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_valuation_method(valuation_method: ValuationMethod) -> ApiResult<()> {
+    check_rate_limit()?;
+    CONFIG.with(|config| config.borrow_mut().valuation_method = valuation_method);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_barcode_format(barcode_format: Option<BarcodeFormat>) -> ApiResult<()> {
+    check_rate_limit()?;
+    CONFIG.with(|config| config.borrow_mut().barcode_format = barcode_format);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_tax_rate(percent: f64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the tax rate.".to_string(),
+        ));
+    }
+    apply_tax_rate(percent)
+}
+
+fn apply_tax_rate(percent: f64) -> ApiResult<()> {
+    if !percent.is_finite() || !(0.0..=100.0).contains(&percent) {
+        return Err(BusinessError::InvalidInput(
+            "Tax rate must be between 0 and 100 percent.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().tax_rate_percent = percent);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_markdown_min_margin(percent: f64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the markdown margin.".to_string(),
+        ));
+    }
+    if !percent.is_finite() || percent < 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Minimum margin must be zero or greater.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().markdown_min_margin_percent = percent);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_min_margin(min_margin_percent: Option<f64>, margin_policy: MarginPolicy) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the minimum margin.".to_string(),
+        ));
+    }
+    if min_margin_percent.is_some_and(|percent| !percent.is_finite() || !(0.0..100.0).contains(&percent)) {
+        return Err(BusinessError::InvalidInput(
+            "Minimum margin must be at least 0 and below 100 percent.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        config.min_margin_percent = min_margin_percent;
+        config.margin_policy = margin_policy;
+    });
+    Ok(())
+}
+
+// Every path that lowers a price goes through here. Below the margin floor the price is rejected or raised to the
+// floor, per the margin policy; prices at or above the current one are never touched.
+fn effective_unit_price(item: &InventoryItem, current_price: f64, requested: f64) -> ApiResult<f64> {
+    let (min_margin, policy) = CONFIG.with(|config| {
+        let config = config.borrow();
+        (config.min_margin_percent, config.margin_policy)
+    });
+    let (Some(min_margin), Some(cost)) = (min_margin, item.cost_price) else {
+        return Ok(requested);
+    };
+    let floor = cost / (1.0 - min_margin / 100.0);
+    if requested >= floor || requested >= current_price {
+        return Ok(requested);
+    }
+    match policy {
+        MarginPolicy::Reject => Err(BusinessError::MarginTooLow(format!(
+            "Price {} for item: {} is below the minimum margin price of {}.",
+            format_amount(requested),
+            item.name,
+            format_amount(floor)
+        ))),
+        MarginPolicy::Cap => Ok(floor.min(current_price)),
+    }
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_exchange_rate(code: String, rate_to_base: f64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can set exchange rates.".to_string(),
+        ));
+    }
+    let code = normalize_currency_code(&code)?;
+    if !rate_to_base.is_finite() || rate_to_base <= 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Exchange rate must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| {
+        let mut config = config.borrow_mut();
+        if config.currency.code == code {
+            return Err(BusinessError::InvalidInput(format!("{} is the base currency.", code)));
+        }
+        config.exchange_rates.insert(code, rate_to_base);
+        Ok(())
+    })
+}
+
+fn normalize_currency_code(code: &str) -> ApiResult<String> {
+    let code = code.trim().to_uppercase();
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(BusinessError::InvalidInput(
+            "Currency code must be a three-letter ISO 4217 code.".to_string(),
+        ));
+    }
+    Ok(code)
+}
+
+fn resolve_sale_currency(currency: Option<String>) -> ApiResult<Option<(String, f64)>> {
+    let Some(code) = currency else {
+        return Ok(None);
+    };
+    let code = normalize_currency_code(&code)?;
+    CONFIG.with(|config| {
+        let config = config.borrow();
+        if config.currency.code == code {
+            return Ok(None);
+        }
+        config
+            .exchange_rates
+            .get(&code)
+            .map(|rate| Some((code.clone(), *rate)))
+            .ok_or_else(|| {
+                BusinessError::InvalidInput(format!("No exchange rate is configured for currency {}.", code))
+            })
+    })
+}
+
+fn foreign_total(currency: &Option<(String, f64)>, total_amount: f64) -> Option<ForeignTotal> {
+    currency.as_ref().map(|(code, rate_to_base)| ForeignTotal {
+        code: code.clone(),
+        rate_to_base: *rate_to_base,
+        amount: total_amount / rate_to_base,
+    })
+}
+
+fn format_amount(amount: f64) -> String {
+    let decimal_places = CONFIG.with(|config| config.borrow().currency.decimal_places) as usize;
+    format!("{:.*}", decimal_places, amount)
+}
+
+fn round_currency(amount: f64) -> f64 {
+    let decimal_places = CONFIG.with(|config| config.borrow().currency.decimal_places);
+    let factor = 10f64.powi(decimal_places as i32);
+    (amount * factor).round() / factor
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_allow_public_read(allow: bool) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change public read access.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().allow_public_read = allow);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_allow_zero_initial_stock(allow: bool) -> ApiResult<()> {
+    check_rate_limit()?;
+    CONFIG.with(|config| config.borrow_mut().allow_zero_initial_stock = allow);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_min_sale_amount(amount: f64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can set the minimum sale amount.".to_string(),
+        ));
+    }
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Minimum sale amount must be zero or greater.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().min_sale_amount = amount);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_monthly_target(amount: f64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Monthly target must be zero or greater.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().monthly_target = amount);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_reservation_ttl(reservation_ttl_ns: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if reservation_ttl_ns == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Reservation TTL must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().reservation_ttl_ns = reservation_ttl_ns);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_pending_sale_window(pending_sale_window_ns: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if pending_sale_window_ns == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Pending sale window must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().pending_sale_window_ns = pending_sale_window_ns);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_sale_amend_window(sale_amend_window_ns: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    CONFIG.with(|config| config.borrow_mut().sale_amend_window_ns = sale_amend_window_ns);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_sales_retention(sales_retention_ns: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change sales retention.".to_string(),
+        ));
+    }
+    if sales_retention_ns == Some(0) {
+        return Err(BusinessError::InvalidInput(
+            "Sales retention must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().sales_retention_ns = sales_retention_ns);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_snapshot_interval(snapshot_interval_ns: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can schedule snapshots.".to_string(),
+        ));
+    }
+    if snapshot_interval_ns == Some(0) {
+        return Err(BusinessError::InvalidInput(
+            "Snapshot interval must be greater than zero.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().snapshot_interval_ns = snapshot_interval_ns);
+    Ok(())
+}
+
+fn auto_snapshot() {
+    let Some(interval) = CONFIG.with(|config| config.borrow().snapshot_interval_ns) else {
+        return;
+    };
+    let last = SNAPSHOTS.with(|snapshots| snapshots.borrow().last().map_or(0, |snapshot| snapshot.timestamp));
+    if time().saturating_sub(last) >= interval {
+        write_snapshot();
+    }
+}
+
+// Snapshots rotate through `SNAPSHOT_SLOTS` stable memories, so the oldest is overwritten by the newest.
+fn write_snapshot() -> SnapshotInfo {
+    let id = SNAPSHOTS.with(|snapshots| snapshots.borrow().last().map_or(1, |snapshot| snapshot.id + 1));
+    let sales = SALES.with(|sales| sales.borrow().iter_all().collect());
+    let mut state = capture_state(sales);
+    state.snapshots = Vec::new();
+    let size_bytes = write_state(snapshot_memory_id(id), &state);
+
+    let info = SnapshotInfo {
+        id,
+        timestamp: time(),
+        size_bytes,
+    };
+    SNAPSHOTS.with(|snapshots| {
+        let mut snapshots = snapshots.borrow_mut();
+        snapshots.retain(|snapshot| snapshot.id + SNAPSHOT_SLOTS > id);
+        snapshots.push(info.clone());
+    });
+    info
+}
+
+fn snapshot_memory_id(id: u64) -> MemoryId {
+    MemoryId::new(FIRST_SNAPSHOT_MEMORY_ID + (id % SNAPSHOT_SLOTS) as u8)
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn take_snapshot() -> ApiResult<SnapshotInfo> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can take snapshots.".to_string(),
+        ));
+    }
+    Ok(write_snapshot())
+}
+
+#[query(guard = "caller_can_read")]
+fn list_snapshots() -> Vec<SnapshotInfo> {
+    SNAPSHOTS.with(|snapshots| snapshots.borrow().clone())
+}
+
+// Replaces all state with the snapshot's, keeping the snapshot list itself and moving the catalog version forward.
+#[update(guard = "caller_is_authenticated")]
+fn rollback_to_snapshot(id: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can roll back to a snapshot.".to_string(),
+        ));
+    }
+    if !SNAPSHOTS.with(|snapshots| snapshots.borrow().iter().any(|snapshot| snapshot.id == id)) {
+        return Err(BusinessError::NotFound(format!("Snapshot {} not found.", id)));
+    }
+    let mut state = read_state(snapshot_memory_id(id));
+    let snapshot_sales = std::mem::take(&mut state.sales);
+    state.snapshots = SNAPSHOTS.with(|snapshots| snapshots.borrow().clone());
+    state.catalog_version = state.catalog_version.max(CATALOG_VERSION.load(Ordering::Relaxed));
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let kept: BTreeSet<u64> = snapshot_sales.iter().map(|sale| sale.seq).collect();
+        let current: Vec<u64> = sales.iter_all().map(|sale| sale.seq).collect();
+        for seq in current.into_iter().filter(|seq| !kept.contains(seq)) {
+            sales.remove(seq);
+        }
+        for sale in &snapshot_sales {
+            sales.insert(sale);
+        }
+    });
+    restore_state(state);
+    bump_catalog_version();
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn archive_old_sales() -> ApiResult<u64> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can archive sales.".to_string(),
+        ));
+    }
+    Ok(archive_expired_sales())
+}
+
+// Rolls sales older than the retention window into monthly totals, a batch at a time; pending sales are kept.
+fn archive_expired_sales() -> u64 {
+    let Some(retention) = CONFIG.with(|config| config.borrow().sales_retention_ns) else {
+        return 0;
+    };
+    let cutoff = time().saturating_sub(retention);
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let expired: Vec<SaleRecord> = sales
+            .iter_all()
+            .filter(|sale| sale.timestamp < cutoff && sale.status != SaleStatus::Pending)
+            .take(MAX_ARCHIVE_BATCH)
+            .collect();
+        ARCHIVED_MONTHS.with(|months| {
+            let mut months = months.borrow_mut();
+            for sale in expired.iter().filter(|sale| sale.status == SaleStatus::Completed && sale.voided.is_none()) {
+                let month_start = period_start(period_index(sale.timestamp, Granularity::Month), Granularity::Month);
+                let month = months.entry(month_start).or_insert(ArchivedMonth {
+                    month_start,
+                    num_sales: 0,
+                    units_sold: 0,
+                    revenue: 0.0,
+                    profit: 0.0,
+                    tax_collected: 0.0,
+                });
+                month.num_sales += 1;
+                month.units_sold += sale.items.iter().map(|item| item.quantity).sum::<u64>();
+                month.revenue += sale.total_amount;
+                month.profit += sale.profit;
+                month.tax_collected += sale.tax_amount;
+            }
+        });
+        for sale in &expired {
+            sales.remove(sale.seq);
+        }
+        expired.len() as u64
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn get_archived_summary() -> Vec<ArchivedMonth> {
+    ARCHIVED_MONTHS.with(|months| months.borrow().values().cloned().collect())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_maintenance_interval(secs: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can change the maintenance interval.".to_string(),
+        ));
+    }
+    if secs == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Maintenance interval must be greater than zero.".to_string(),
+        ));
+    }
+    if secs > MAX_MAINTENANCE_INTERVAL_SECS {
+        return Err(BusinessError::InvalidInput(format!(
+            "Maintenance interval cannot exceed {} seconds.",
+            MAX_MAINTENANCE_INTERVAL_SECS
+        )));
+    }
+    CONFIG.with(|config| config.borrow_mut().maintenance_interval_ns = Some(secs * NANOS_PER_SECOND));
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn disable_maintenance() -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can disable maintenance.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().maintenance_interval_ns = None);
+    Ok(())
+}
+
+#[heartbeat]
+fn heartbeat() {
+    refresh_daily_deal();
+    let Some(interval) = CONFIG.with(|config| config.borrow().maintenance_interval_ns) else {
+        return;
+    };
+    let now = time();
+    if now.saturating_sub(LAST_MAINTENANCE.with(Cell::get)) >= interval {
+        LAST_MAINTENANCE.with(|last| last.set(now));
+        run_maintenance();
+    }
+}
+
+fn run_maintenance() {
+    expire_old_reservations();
+    cancel_expired_pending_sales();
+    archive_expired_sales();
+    auto_snapshot();
+    prune_rate_buckets(time());
+}
+
+fn validate_window(start: u64, end: u64) -> ApiResult<()> {
+    if start > end {
+        return Err(BusinessError::InvalidTimeRange(
+            "Start time must not be after end time.".to_string(),
+        ));
+    }
+    if start > time().saturating_add(MAX_FUTURE_WINDOW_START_NS) {
+        return Err(BusinessError::InvalidTimeRange(
+            "Start time is too far in the future.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_day_cutoff_hour(day_cutoff_hour: u8) -> ApiResult<()> {
+    check_rate_limit()?;
+    if day_cutoff_hour > 23 {
+        return Err(BusinessError::InvalidInput(
+            "Day cutoff hour must be between 0 and 23.".to_string(),
+        ));
+    }
+    CONFIG.with(|config| config.borrow_mut().day_cutoff_hour = day_cutoff_hour);
+    Ok(())
+}
+
+fn local_offset_ns() -> i128 {
+    CONFIG.with(|config| config.borrow().utc_offset_minutes) as i128 * NANOS_PER_MINUTE
+}
+
+// Shift applied to UTC so that business days start at the configured cutoff hour, local time.
+fn business_day_shift_ns() -> i128 {
+    let cutoff_hour = CONFIG.with(|config| config.borrow().day_cutoff_hour) as i128;
+    local_offset_ns() - cutoff_hour * 60 * NANOS_PER_MINUTE
+}
+
+fn local_day_number(timestamp: u64) -> i64 {
+    (timestamp as i128 + local_offset_ns()).div_euclid(NANOS_PER_DAY) as i64
+}
+
+fn business_day_number(timestamp: u64) -> i64 {
+    (timestamp as i128 + business_day_shift_ns()).div_euclid(NANOS_PER_DAY) as i64
+}
+
+fn local_day_start(day: i64) -> u64 {
+    (day as i128 * NANOS_PER_DAY - local_offset_ns()).clamp(0, u64::MAX as i128) as u64
+}
+
+fn business_day_start(day: i64) -> u64 {
+    (day as i128 * NANOS_PER_DAY - business_day_shift_ns()).clamp(0, u64::MAX as i128) as u64
+}
+
+// 1970-01-01 was a Thursday; shifting by three days makes weeks start on Monday.
+fn period_index(timestamp: u64, granularity: Granularity) -> i64 {
+    match granularity {
+        Granularity::Hour => (timestamp as i128 + local_offset_ns()).div_euclid(NANOS_PER_HOUR) as i64,
+        Granularity::Day => business_day_number(timestamp),
+        Granularity::Week => (business_day_number(timestamp) + 3).div_euclid(7),
+        Granularity::Month => {
+            let (year, month, _) = civil_from_days(business_day_number(timestamp));
+            year * 12 + month as i64 - 1
+        }
+    }
+}
+
+fn period_start(index: i64, granularity: Granularity) -> u64 {
+    match granularity {
+        Granularity::Hour => {
+            (index as i128 * NANOS_PER_HOUR - local_offset_ns()).clamp(0, u64::MAX as i128) as u64
+        }
+        Granularity::Day => business_day_start(index),
+        Granularity::Week => business_day_start(index * 7 - 3),
+        Granularity::Month => business_day_start(days_from_civil(
+            index.div_euclid(12),
+            index.rem_euclid(12) as u32 + 1,
+            1,
+        )),
+    }
+}
+
+fn day_bounds(timestamp: u64) -> (u64, u64) {
+    let day = business_day_number(timestamp);
+    (business_day_start(day), business_day_start(day + 1))
+}
+
+// Howard Hinnant's days_from_civil / civil_from_days, counting days from 1970-01-01.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn add_item(
+    name: String,
+    quantity: u64,
+    price: f64,
+    sku: Option<String>,
+    description: Option<String>,
+    client_key: Option<String>,
+    variant: Option<String>,
+) -> ApiResult<ItemId> {
+    check_rate_limit()?;
+    let client_key = client_key.map(|key| key.trim().to_string());
+    if let Some(key) = &client_key {
+        if key.is_empty() || key.len() > MAX_CLIENT_KEY_LENGTH {
+            return Err(BusinessError::InvalidInput(format!(
+                "Client key must be between 1 and {} characters.",
+                MAX_CLIENT_KEY_LENGTH
+            )));
+        }
+        let existing = CLIENT_KEYS.with(|client_keys| client_keys.borrow().get(key).copied());
+        if let Some(id) = existing.filter(|id| INVENTORY.with(|inventory| inventory.borrow().contains_key(id))) {
+            return Ok(id);
+        }
+    }
+
+    let name = normalize_name(&name);
+    if name.is_empty() {
+        return Err(BusinessError::InvalidInput("Item name cannot be empty.".to_string()));
+    }
+    validate_name_length(&name)?;
+    if quantity == 0 && !CONFIG.with(|config| config.borrow().allow_zero_initial_stock) {
+        return Err(BusinessError::ZeroQuantity(
+            "Quantity must be greater than zero.".to_string(),
+        ));
+    }
+    if price <= 0.0 {
+        return Err(BusinessError::InvalidInput("Price must be positive.".to_string()));
+    }
+    check_price_magnitude(price)?;
+    check_currency_precision(price)?;
+    check_quantity_magnitude(quantity)?;
+    let sku = sku.as_deref().map(normalize_sku).transpose()?;
+    sku.as_deref().map(check_barcode).transpose()?;
+    let description = description.as_deref().map(normalize_description).transpose()?.flatten();
+    let variant = variant.as_deref().map(normalize_variant).transpose()?;
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        if let Some(sku) = &sku {
+            if find_item_by_sku(&inventory, sku).is_some() {
+                return Err(BusinessError::Conflict(format!(
+                    "An item with SKU {} already exists.",
+                    sku
+                )));
+            }
+        }
+        if find_item_by_variant(&inventory, &name, variant.as_deref()).is_some() {
+            return Err(BusinessError::Conflict(format!(
+                "An item named {} already exists.",
+                describe_variant(&name, variant.as_deref())
+            )));
+        }
+
+        let id = generate_id();
+        let now = time();
+        inventory.insert(
+            id,
+            InventoryItem {
+                id,
+                name,
+                variant,
+                quantity,
+                price,
+                sku,
+                description,
+                reorder_point: None,
+                target_stock: None,
+                max_stock: None,
+                category: None,
+                tags: Vec::new(),
+                cost_price: None,
+                tax_exempt: false,
+                active: true,
+                lead_time_days: None,
+                safety_stock: None,
+                serialized: false,
+                online_buffer: None,
+                deposit: None,
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        record_movement(&inventory[&id], MovementKind::Initial, 0, quantity, None);
+        if let Some(key) = client_key {
+            CLIENT_KEYS.with(|client_keys| client_keys.borrow_mut().insert(key, id));
+        }
+        Ok(id)
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn update_item(
+    id: ItemId,
+    name: Option<String>,
+    quantity: Option<u64>,
+    price: Option<f64>,
+    sku: Option<String>,
+    description: Option<String>,
+) -> ApiResult<()> {
+    check_rate_limit()?;
+    apply_item_patch(
+        id,
+        ItemPatch {
+            name,
+            quantity,
+            price,
+            sku,
+            description,
+            ..ItemPatch::default()
+        },
+    )
+}
+
+// The patch is applied to a copy and only stored once every field has passed validation.
+#[update(guard = "caller_is_authenticated")]
+fn patch_item(id: ItemId, patch: ItemPatch) -> ApiResult<()> {
+    check_rate_limit()?;
+    apply_item_patch(id, patch)
+}
+
+fn apply_item_patch(id: ItemId, patch: ItemPatch) -> ApiResult<()> {
+    if patch.is_empty() {
+        return Err(BusinessError::NothingToUpdate);
+    }
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let current = inventory
+            .get(&id)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+        let mut item = current.clone();
+
+        if let Some(new_name) = patch.name {
+            let new_name = normalize_name(&new_name);
+            if new_name.is_empty() {
+                return Err(BusinessError::InvalidInput("Updated name cannot be empty.".to_string()));
+            }
+            validate_name_length(&new_name)?;
+            if find_item_by_variant(&inventory, &new_name, item.variant.as_deref())
+                .is_some_and(|existing| existing != id)
+            {
+                return Err(BusinessError::Conflict(format!(
+                    "An item named {} already exists.",
+                    describe_variant(&new_name, item.variant.as_deref())
+                )));
+            }
+            item.name = new_name;
+        }
+        if let Some(new_quantity) = patch.quantity {
+            if new_quantity == 0 {
+                return Err(BusinessError::ZeroQuantity(
+                    "Updated quantity must be greater than zero.".to_string(),
+                ));
+            }
+            check_not_serialized(&item)?;
+            check_quantity_magnitude(new_quantity)?;
+            check_capacity(&item, new_quantity)?;
+            item.quantity = new_quantity;
+        }
+        if let Some(cost) = patch.cost_price {
+            if !cost.is_finite() || cost < 0.0 {
+                return Err(BusinessError::InvalidInput(
+                    "Cost price must be zero or greater.".to_string(),
+                ));
+            }
+            item.cost_price = Some(cost);
+        }
+        if let Some(new_price) = patch.price {
+            if new_price <= 0.0 {
+                return Err(BusinessError::InvalidInput(
+                    "Updated price must be positive.".to_string(),
+                ));
+            }
+            check_price_magnitude(new_price)?;
+            check_currency_precision(new_price)?;
+            item.price = effective_unit_price(&item, current.price, new_price)?;
+        }
+        if let Some(new_sku) = patch.sku {
+            let new_sku = normalize_sku(&new_sku)?;
+            check_barcode(&new_sku)?;
+            if find_item_by_sku(&inventory, &new_sku).is_some_and(|existing| existing != id) {
+                return Err(BusinessError::Conflict(format!(
+                    "An item with SKU {} already exists.",
+                    new_sku
+                )));
+            }
+            item.sku = Some(new_sku);
+        }
+        if let Some(new_description) = patch.description {
+            item.description = normalize_description(&new_description)?;
+        }
+        if let Some(new_category) = patch.category {
+            item.category = Some(normalize_category(&new_category)?);
+        }
+        if let Some(reorder_point) = patch.reorder_point {
+            item.reorder_point = Some(reorder_point);
+        }
+        if let Some(target_stock) = patch.target_stock {
+            item.target_stock = Some(target_stock);
+        }
+        if let (Some(point), Some(target)) = (item.reorder_point, item.target_stock) {
+            if target < point {
+                return Err(BusinessError::InvalidInput(
+                    "Target stock cannot be below the reorder point.".to_string(),
+                ));
+            }
+        }
+
+        record_movement(&item, MovementKind::Adjustment, current.quantity, item.quantity, None);
+        item.updated_at = time();
+        bump_catalog_version();
+        inventory.insert(id, item);
+        Ok(())
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn update_quantity_cas(id: ItemId, expected: u64, new: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    if new == 0 {
+        return Err(BusinessError::ZeroQuantity(
+            "Updated quantity must be greater than zero.".to_string(),
+        ));
+    }
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            if item.quantity != expected {
+                return Err(BusinessError::ConcurrentModification { current: item.quantity });
+            }
+            check_not_serialized(item)?;
+            check_quantity_magnitude(new)?;
+            check_capacity(item, new)?;
+            record_movement(item, MovementKind::Adjustment, item.quantity, new, None);
+            item.quantity = new;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+fn check_price_magnitude(price: f64) -> ApiResult<()> {
+    let max_price = CONFIG.with(|config| config.borrow().max_price);
+    if !price.is_finite() || price > max_price {
+        return Err(BusinessError::ValueTooLarge(format!(
+            "Price {} exceeds the maximum allowed price of {}.",
+            price, max_price
+        )));
+    }
+    Ok(())
+}
+
+// Zero-decimal currencies such as JPY have no minor unit, so fractional amounts cannot be charged.
+fn check_currency_precision(amount: f64) -> ApiResult<()> {
+    let currency = CONFIG.with(|config| config.borrow().currency.clone());
+    if currency.decimal_places == 0 && amount.fract() != 0.0 {
+        return Err(BusinessError::InvalidInput(format!(
+            "Amount {} must be a whole number of {}, which has no minor unit.",
+            amount, currency.code
+        )));
+    }
+    Ok(())
+}
+
+fn check_quantity_magnitude(quantity: u64) -> ApiResult<()> {
+    let max_quantity = CONFIG.with(|config| config.borrow().max_quantity_per_item);
+    if quantity > max_quantity {
+        return Err(BusinessError::ValueTooLarge(format!(
+            "Quantity {} exceeds the maximum allowed quantity of {} per item.",
+            quantity, max_quantity
+        )));
+    }
+    Ok(())
+}
+
+fn check_not_serialized(item: &InventoryItem) -> ApiResult<()> {
+    if item.serialized {
+        return Err(BusinessError::InvalidInput(format!(
+            "Item: {} is serialized; add stock with add_serial.",
+            item.name
+        )));
+    }
+    Ok(())
+}
+
+fn check_capacity(item: &InventoryItem, attempted: u64) -> ApiResult<()> {
+    match item.max_stock {
+        Some(max) if attempted > max => Err(BusinessError::ExceedsCapacity { max, attempted }),
+        _ => Ok(()),
+    }
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn restock_item(id: ItemId, amount: u64, unit_cost: Option<f64>) -> ApiResult<u64> {
+    check_rate_limit()?;
+    restock(id, amount, unit_cost)
+}
+
+fn restock(id: ItemId, amount: u64, unit_cost: Option<f64>) -> ApiResult<u64> {
+    if amount == 0 {
+        return Err(BusinessError::ZeroQuantity(
+            "Restock amount must be greater than zero.".to_string(),
+        ));
+    }
+    if let Some(cost) = unit_cost {
+        if !cost.is_finite() || cost < 0.0 {
+            return Err(BusinessError::InvalidInput(
+                "Cost price must be zero or greater.".to_string(),
+            ));
+        }
+    }
+    let valuation_method = CONFIG.with(|config| config.borrow().valuation_method);
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            check_not_serialized(item)?;
+            let new_quantity = item.quantity.checked_add(amount).ok_or_else(|| {
+                BusinessError::ValueTooLarge(format!("Restocking would overflow the quantity of item: {}", item.name))
+            })?;
+            check_quantity_magnitude(new_quantity)?;
+            check_capacity(item, new_quantity)?;
+            record_movement(item, MovementKind::Restock, item.quantity, new_quantity, None);
+            if let Some(incoming_cost) = unit_cost {
+                item.cost_price = Some(match (valuation_method, item.cost_price) {
+                    (ValuationMethod::WeightedAverage, Some(current_cost)) => {
+                        (current_cost * item.quantity as f64 + incoming_cost * amount as f64) / new_quantity as f64
+                    }
+                    _ => incoming_cost,
+                });
+            }
+            item.quantity = new_quantity;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(new_quantity)
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn bulk_restock(lines: Vec<(ItemId, u64)>) -> ApiResult<Vec<ApiResult<u64>>> {
+    check_rate_limit()?;
+    Ok(merge_lines(lines)
+        .into_iter()
+        .map(|(id, amount)| restock(id, amount, None))
+        .collect())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_max_stock(id: ItemId, max_stock: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            if let Some(max) = max_stock {
+                if item.quantity > max {
+                    return Err(BusinessError::Conflict(format!(
+                        "Item: {} already holds {} units, above the requested capacity of {}.",
+                        item.name, item.quantity, max
+                    )));
+                }
+            }
+            item.max_stock = max_stock;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn apply_price_list(changes: Vec<(ItemId, f64)>, dry_run: bool) -> ApiResult<PriceListReport> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let now = time();
+        let mut report = PriceListReport {
+            dry_run,
+            outcomes: Vec::with_capacity(changes.len()),
+            applied: 0,
+            rejected: 0,
+        };
+
+        for (id, new_price) in changes {
+            let item = inventory.get_mut(&id);
+            let old_price = item.as_ref().map(|item| item.price);
+            let priced = match item {
+                None => Err(BusinessError::NotFound(format!("Item with ID {} not found.", id))),
+                Some(item) => {
+                    let price = if !new_price.is_finite() || new_price <= 0.0 {
+                        Err(BusinessError::InvalidInput("Price must be positive.".to_string()))
+                    } else {
+                        check_price_magnitude(new_price)
+                            .and_then(|_| check_currency_precision(new_price))
+                            .and_then(|_| effective_unit_price(item, item.price, new_price))
+                    };
+                    price.map(|price| (item, price))
+                }
+            };
+
+            let (applied_price, rejection) = match priced {
+                Ok((item, price)) => {
+                    if !dry_run {
+                        item.price = price;
+                        item.updated_at = now;
+                        bump_catalog_version();
+                    }
+                    report.applied += 1;
+                    (Some(price), None)
+                }
+                Err(err) => {
+                    report.rejected += 1;
+                    (None, Some(err))
+                }
+            };
+            report.outcomes.push(PriceChangeOutcome {
+                id,
+                old_price,
+                new_price,
+                applied_price,
+                rejection,
+            });
+        }
+        Ok(report)
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_reorder_levels(id: ItemId, reorder_point: Option<u64>, target_stock: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if let (Some(point), Some(target)) = (reorder_point, target_stock) {
+        if target < point {
+            return Err(BusinessError::InvalidInput(
+                "Target stock cannot be below the reorder point.".to_string(),
+            ));
+        }
+    }
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.reorder_point = reorder_point;
+            item.target_stock = target_stock;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_category(id: ItemId, category: Option<String>) -> ApiResult<()> {
+    check_rate_limit()?;
+    let category = category.as_deref().map(normalize_category).transpose()?;
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.category = category;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_tags(id: ItemId, tags: Vec<String>) -> ApiResult<()> {
+    check_rate_limit()?;
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in &tags {
+        let tag = normalize_tag(tag)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS_PER_ITEM {
+        return Err(BusinessError::InvalidInput(format!(
+            "An item cannot have more than {} tags.",
+            MAX_TAGS_PER_ITEM
+        )));
+    }
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.tags = normalized;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn tag_items(filter: InventoryFilter, tag: String) -> ApiResult<u64> {
+    check_rate_limit()?;
+    let criteria = FilterCriteria::new(&filter)?;
+    let tag = normalize_tag(&tag)?;
+    let now = time();
+
+    INVENTORY.with(|inventory| {
+        let mut tagged = 0;
+        for item in inventory.borrow_mut().values_mut() {
+            if !criteria.matches(item) || item.tags.contains(&tag) || item.tags.len() >= MAX_TAGS_PER_ITEM {
+                continue;
+            }
+            item.tags.push(tag.clone());
+            item.updated_at = now;
+            bump_catalog_version();
+            tagged += 1;
+        }
+        Ok(tagged)
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_cost(id: ItemId, cost_price: Option<f64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if let Some(cost) = cost_price {
+        if !cost.is_finite() || cost < 0.0 {
+            return Err(BusinessError::InvalidInput(
+                "Cost price must be zero or greater.".to_string(),
+            ));
+        }
+    }
+
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.cost_price = cost_price;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_active(id: ItemId, active: bool) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.active = active;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_featured(ids: Vec<ItemId>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !is_owner(&caller()) {
+        return Err(BusinessError::Unauthorized(
+            "Only the owner or a canister controller can curate featured items.".to_string(),
+        ));
+    }
+    if ids.len() > MAX_FEATURED_ITEMS {
+        return Err(BusinessError::InvalidInput(format!(
+            "At most {} items can be featured.",
+            MAX_FEATURED_ITEMS
+        )));
+    }
+    for (index, id) in ids.iter().enumerate() {
+        if ids[..index].contains(id) {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item with ID {} is listed more than once.",
+                id
+            )));
+        }
+        if !INVENTORY.with(|inventory| inventory.borrow().contains_key(id)) {
+            return Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)));
+        }
+    }
+    CONFIG.with(|config| config.borrow_mut().featured_items = ids);
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_safety_stock(id: ItemId, safety_stock: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.safety_stock = safety_stock;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_online_buffer(id: ItemId, online_buffer: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.online_buffer = online_buffer.filter(|buffer| *buffer > 0);
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_lead_time(id: ItemId, lead_time_days: Option<u64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.lead_time_days = lead_time_days;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_variant(id: ItemId, variant: Option<String>) -> ApiResult<()> {
+    check_rate_limit()?;
+    let variant = variant.as_deref().map(normalize_variant).transpose()?;
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let name = inventory
+            .get(&id)
+            .map(|item| item.name.clone())
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+        if find_item_by_variant(&inventory, &name, variant.as_deref()).is_some_and(|existing| existing != id) {
+            return Err(BusinessError::Conflict(format!(
+                "An item named {} already exists.",
+                describe_variant(&name, variant.as_deref())
+            )));
+        }
+        if let Some(item) = inventory.get_mut(&id) {
+            item.variant = variant;
+            item.updated_at = time();
+            bump_catalog_version();
+        }
+        Ok(())
+    })
+}
+
+// Serials registered on an unserialized item label its existing units; once serialized, each new serial adds a unit.
+#[update(guard = "caller_is_authenticated")]
+fn add_serial(item_id: ItemId, serial: String) -> ApiResult<()> {
+    check_rate_limit()?;
+    let serial = serial.trim().to_string();
+    if serial.is_empty() || serial.chars().count() > MAX_SERIAL_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Serial must be between 1 and {} characters.",
+            MAX_SERIAL_LENGTH
+        )));
+    }
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let item = inventory
+            .get_mut(&item_id)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", item_id)))?;
+        SERIALS.with(|serials| {
+            let mut serials = serials.borrow_mut();
+            let units = serials.entry(item_id).or_default();
+            if units.iter().any(|unit| unit.serial == serial) {
+                return Err(BusinessError::Conflict(format!(
+                    "Serial {} is already registered for item: {}.",
+                    serial, item.name
+                )));
+            }
+            let in_stock = units.iter().filter(|unit| unit.status == SerialStatus::InStock).count() as u64;
+            if item.serialized {
+                let new_quantity = item.quantity.saturating_add(1);
+                check_quantity_magnitude(new_quantity)?;
+                check_capacity(item, new_quantity)?;
+                record_movement(item, MovementKind::Restock, item.quantity, new_quantity, None);
+                item.quantity = new_quantity;
+            } else if in_stock >= item.quantity {
+                return Err(BusinessError::Conflict(format!(
+                    "Every unit of item: {} already has a serial number.",
+                    item.name
+                )));
+            }
+            units.push(SerialUnit {
+                serial,
+                status: SerialStatus::InStock,
+                sale_seq: None,
+            });
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        })
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_serialized(id: ItemId, serialized: bool) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let item = inventory
+            .get_mut(&id)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+        if serialized && !item.serialized {
+            let in_stock = SERIALS.with(|serials| {
+                serials.borrow().get(&id).map_or(0, |units| {
+                    units.iter().filter(|unit| unit.status == SerialStatus::InStock).count() as u64
+                })
+            });
+            if in_stock != item.quantity {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Item: {} has {} units but {} registered serial numbers.",
+                    item.name, item.quantity, in_stock
+                )));
+            }
+        }
+        item.serialized = serialized;
+        item.updated_at = time();
+        bump_catalog_version();
+        Ok(())
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn get_serials(item_id: ItemId) -> Vec<SerialUnit> {
+    SERIALS.with(|serials| serials.borrow().get(&item_id).cloned().unwrap_or_default())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_tax_exempt(id: ItemId, tax_exempt: bool) -> ApiResult<()> {
+    check_rate_limit()?;
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.tax_exempt = tax_exempt;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn set_item_deposit(id: ItemId, deposit: Option<f64>) -> ApiResult<()> {
+    check_rate_limit()?;
+    if let Some(deposit) = deposit {
+        if !deposit.is_finite() || deposit <= 0.0 {
+            return Err(BusinessError::InvalidInput("Deposit must be positive.".to_string()));
+        }
+        check_price_magnitude(deposit)?;
+        check_currency_precision(deposit)?;
+    }
+    INVENTORY.with(|inventory| {
+        if let Some(item) = inventory.borrow_mut().get_mut(&id) {
+            item.deposit = deposit;
+            item.updated_at = time();
+            bump_catalog_version();
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+        }
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn recategorize(from: Option<String>, to: String) -> ApiResult<u64> {
+    check_rate_limit()?;
+    let from = from.as_deref().map(normalize_category).transpose()?;
+    let to = normalize_category(&to)?;
+
+    INVENTORY.with(|inventory| {
+        let now = time();
+        let mut moved = 0;
+        for item in inventory.borrow_mut().values_mut() {
+            if item.category == from {
+                item.category = Some(to.clone());
+                item.updated_at = now;
+                bump_catalog_version();
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    })
+}
+
+// Deletion is refused while a pending sale holds the item; otherwise its featured slot and open holds go with it.
+#[update(guard = "caller_is_authenticated")]
+fn remove_item(id: ItemId) -> ApiResult<()> {
+    check_rate_limit()?;
+    if !INVENTORY.with(|inventory| inventory.borrow().contains_key(&id)) {
+        return Err(BusinessError::NotFound(format!("Item with ID {} not found.", id)));
+    }
+    let pending_sale = RESERVATIONS.with(|reservations| {
+        reservations
+            .borrow()
+            .values()
+            .filter(|reservation| reservation.items.iter().any(|(item_id, _)| *item_id == id))
+            .find_map(|reservation| reservation.sale_seq)
+    });
+    if let Some(sale_seq) = pending_sale {
+        return Err(BusinessError::Conflict(format!(
+            "Item with ID {} is held by pending sale {}; confirm or cancel it first.",
+            id, sale_seq
+        )));
+    }
+
+    INVENTORY.with(|inventory| inventory.borrow_mut().remove(&id));
+    SERIALS.with(|serials| serials.borrow_mut().remove(&id));
+    CONFIG.with(|config| config.borrow_mut().featured_items.retain(|featured| *featured != id));
+    RESERVATIONS.with(|reservations| {
+        let mut reservations = reservations.borrow_mut();
+        for reservation in reservations.values_mut() {
+            reservation.items.retain(|(item_id, _)| *item_id != id);
+        }
+        reservations.retain(|_, reservation| !reservation.items.is_empty());
+    });
+    bump_catalog_version();
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn merge_items(keep: ItemId, merge: ItemId) -> ApiResult<()> {
+    check_rate_limit()?;
+    if keep == merge {
+        return Err(BusinessError::InvalidInput(
+            "Cannot merge an item into itself.".to_string(),
+        ));
+    }
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        if !inventory.contains_key(&keep) {
+            return Err(BusinessError::NotFound(format!("Item with ID {} not found.", keep)));
+        }
+        let serialized = [keep, merge]
+            .iter()
+            .filter_map(|id| inventory.get(id))
+            .find(|item| item.serialized);
+        if let Some(item) = serialized {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} is serialized and cannot be merged.",
+                item.name
+            )));
+        }
+        let incoming = inventory
+            .get(&merge)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", merge)))?
+            .quantity;
+        if let Some(item) = inventory.get(&keep) {
+            check_capacity(item, item.quantity.saturating_add(incoming))?;
+        }
+        let merged = inventory
+            .remove(&merge)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", merge)))?;
+        record_movement(&merged, MovementKind::Transfer, merged.quantity, 0, Some(keep));
+        if let Some(item) = inventory.get_mut(&keep) {
+            let new_quantity = item.quantity.saturating_add(merged.quantity);
+            record_movement(item, MovementKind::Transfer, item.quantity, new_quantity, Some(merge));
+            item.quantity = new_quantity;
+            item.updated_at = time();
+            bump_catalog_version();
+        }
+        Ok(())
+    })?;
+
+    CONFIG.with(|config| config.borrow_mut().featured_items.retain(|featured| *featured != merge));
+    relink_item_history(merge, keep);
+    Ok(())
+}
+
+fn relink_item_history(from: ItemId, to: ItemId) {
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let affected: Vec<SaleRecord> = sales
+            .iter_all()
+            .filter(|sale| sale.items.iter().any(|item| item.id == from))
+            .collect();
+        for mut sale in affected {
+            for item in sale.items.iter_mut().filter(|item| item.id == from) {
+                item.id = to;
+            }
+            sales.insert(&sale);
+        }
+    });
+    RETURNS.with(|returns| {
+        for record in returns.borrow_mut().iter_mut() {
+            for item in record.items.iter_mut().filter(|item| item.id == from) {
+                item.id = to;
+            }
+        }
+    });
+    RESERVATIONS.with(|reservations| {
+        for reservation in reservations.borrow_mut().values_mut() {
+            for (id, _) in reservation.items.iter_mut().filter(|(id, _)| *id == from) {
+                *id = to;
+            }
+        }
+    });
+}
+
+// Rebuilds a hard-deleted item from its latest sale line at zero stock, then points its history at the new ID.
+#[update(guard = "caller_is_authenticated")]
+fn recover_item(old_id: ItemId) -> ApiResult<ItemId> {
+    check_rate_limit()?;
+    if INVENTORY.with(|inventory| inventory.borrow().contains_key(&old_id)) {
+        return Err(BusinessError::Conflict(format!(
+            "Item with ID {} still exists.",
+            old_id
+        )));
+    }
+    let last_sold = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter_all()
+            .filter_map(|sale| sale.items.into_iter().find(|item| item.id == old_id))
+            .last()
+    });
+    let sold = last_sold
+        .ok_or_else(|| BusinessError::InvalidInput(format!("Item with ID {} does not appear in any sale.", old_id)))?;
+
+    let id = INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        if find_item_by_variant(&inventory, &sold.name, None).is_some() {
+            return Err(BusinessError::Conflict(format!(
+                "An item named {} already exists.",
+                sold.name
+            )));
+        }
+        let id = generate_id();
+        let now = time();
+        inventory.insert(
+            id,
+            InventoryItem {
+                id,
+                name: sold.name,
+                variant: None,
+                quantity: 0,
+                price: sold.list_price,
+                sku: None,
+                description: None,
+                reorder_point: None,
+                target_stock: None,
+                max_stock: None,
+                category: sold.category,
+                tags: Vec::new(),
+                cost_price: sold.unit_cost,
+                tax_exempt: false,
+                active: true,
+                lead_time_days: None,
+                safety_stock: None,
+                serialized: false,
+                online_buffer: None,
+                deposit: Some(sold.deposit).filter(|deposit| *deposit > 0.0),
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        record_movement(&inventory[&id], MovementKind::Initial, 0, 0, None);
+        Ok(id)
+    })?;
+
+    relink_item_history(old_id, id);
+    Ok(id)
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn record_sale(items: Vec<(ItemId, u64)>, options: Option<SaleOptions>) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    process_sale(
+        items.into_iter().map(SaleLine::from).collect(),
+        options.unwrap_or_default(),
+    )
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn record_sale_lines(lines: Vec<SaleLine>, options: Option<SaleOptions>) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    process_sale(lines, options.unwrap_or_default())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn quick_sell(sku: String) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    let sku = normalize_sku(&sku)?;
+    let id = INVENTORY
+        .with(|inventory| find_item_by_sku(&inventory.borrow(), &sku))
+        .ok_or_else(|| BusinessError::NotFound(format!("Item with SKU {} not found.", sku)))?;
+    process_sale(vec![SaleLine::from((id, 1))], SaleOptions::default())
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+struct SaleOptions {
+    currency: Option<String>,
+    note: Option<String>,
+    pending: Option<bool>,
+    override_minimum: Option<bool>,
+    channel: Option<Channel>,
+    partial_fulfillment: Option<bool>,
+}
+
+// A pending sale prices its lines and holds the stock in a reservation until it is confirmed or cancelled.
+fn process_sale(lines: Vec<SaleLine>, options: SaleOptions) -> ApiResult<SaleRecord> {
+    let mut lines = validate_sale_lines(lines)?;
+    let currency = resolve_sale_currency(options.currency)?;
+    let note = options.note.as_deref().map(normalize_sale_note).transpose()?.flatten();
+    let channel = normalize_channel(options.channel.unwrap_or_default())?;
+    let pending = options.pending.unwrap_or(false);
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        let skipped = if options.partial_fulfillment.unwrap_or(false) {
+            take_short_lines(&inventory, &mut lines)?
+        } else {
+            Vec::new()
+        };
+        check_sale_stock(&inventory, &lines, &BTreeMap::new())?;
+        enforce_line_margins(&inventory, &mut lines)?;
+        check_discount_reasons(&inventory, &lines)?;
+        if !options.override_minimum.unwrap_or(false) {
+            check_sale_minimum(&inventory, &lines)?;
+        }
+
+        if pending {
+            if let Some(item) = lines
+                .iter()
+                .filter_map(|line| inventory.get(&line.id))
+                .find(|item| item.serialized)
+            {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Item: {} is serialized and cannot be sold as pending.",
+                    item.name
+                )));
+            }
+        }
+
+        // Only allocated once every check has passed, so rejected sales leave no gaps in the sequence.
+        let seq = next_sale_seq();
+        let now = time();
+        let (sale_items, total_amount) = if pending {
+            let held = merge_lines(lines.iter().map(|line| (line.id, line.quantity)).collect());
+            let reservation_id = next_reservation_id();
+            RESERVATIONS.with(|reservations| {
+                reservations.borrow_mut().insert(
+                    reservation_id,
+                    Reservation {
+                        id: reservation_id,
+                        items: held,
+                        created_at: now,
+                        sale_seq: Some(seq),
+                    },
+                )
+            });
+            price_sale_lines(&inventory, &lines)
+        } else {
+            apply_sale_lines(&mut inventory, lines, seq)
+        };
+        let sale_record = SaleRecord {
+            seq,
+            timestamp: now,
+            profit: sale_items_profit(&sale_items),
+            tax_amount: sale_items_tax(&sale_items),
+            deposit_total: sale_items_deposit(&sale_items),
+            items: sale_items,
+            total_amount,
+            foreign_total: foreign_total(&currency, total_amount),
+            note,
+            cashier: Some(caller()),
+            status: if pending { SaleStatus::Pending } else { SaleStatus::Completed },
+            voided: None,
+            channel,
+            skipped,
+        };
+
+        debug_assert!(sale_total_matches(&sale_record));
+        SALES.with(|sales| sales.borrow_mut().insert(&sale_record));
+
+        Ok(sale_record)
+    })
+}
+
+fn check_sale_minimum(inventory: &BTreeMap<ItemId, InventoryItem>, lines: &[SaleLine]) -> ApiResult<()> {
+    let min_sale_amount = CONFIG.with(|config| config.borrow().min_sale_amount);
+    let (_, total_amount) = price_sale_lines(inventory, lines);
+    if total_amount < min_sale_amount {
+        return Err(BusinessError::BelowMinimumSale {
+            total: total_amount,
+            minimum: min_sale_amount,
+        });
+    }
+    Ok(())
+}
+
+fn normalize_sale_note(note: &str) -> ApiResult<Option<String>> {
+    let note = note.trim();
+    if note.chars().count() > MAX_SALE_NOTE_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Sale note cannot exceed {} characters.",
+            MAX_SALE_NOTE_LENGTH
+        )));
+    }
+    Ok(Some(note.to_string()).filter(|note| !note.is_empty()))
+}
+
+// Best-effort sales drop inactive items and those without enough unreserved stock above their safety stock;
+// every other check still applies to the lines kept.
+fn take_short_lines(
+    inventory: &BTreeMap<ItemId, InventoryItem>,
+    lines: &mut Vec<SaleLine>,
+) -> ApiResult<Vec<(ItemId, u64)>> {
+    let requested = merge_lines(lines.iter().map(|line| (line.id, line.quantity)).collect());
+    let skipped: Vec<(ItemId, u64)> = requested
+        .into_iter()
+        .filter(|(id, quantity)| {
+            inventory.get(id).is_some_and(|item| {
+                let available = available_quantity(item);
+                !item.active
+                    || available < *quantity
+                    || breached_safety_stock(item, lines, available, *quantity).is_some()
+            })
+        })
+        .collect();
+    lines.retain(|line| !skipped.iter().any(|(id, _)| *id == line.id));
+    if lines.is_empty() {
+        return Err(BusinessError::InvalidInput(
+            "None of the sale's lines can be filled.".to_string(),
+        ));
+    }
+    Ok(skipped)
+}
+
+fn normalize_channel(channel: Channel) -> ApiResult<Channel> {
+    match channel {
+        Channel::Other(name) => {
+            let name = normalize_name(&name);
+            if name.is_empty() {
+                return Err(BusinessError::InvalidInput("Channel name cannot be empty.".to_string()));
+            }
+            if name.chars().count() > MAX_CHANNEL_LENGTH {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Channel name cannot exceed {} characters.",
+                    MAX_CHANNEL_LENGTH
+                )));
+            }
+            Ok(Channel::Other(name))
+        }
+        channel => Ok(channel),
+    }
+}
+
+const SALE_TOTAL_TOLERANCE: f64 = 0.01;
+
+fn sale_total_matches(sale: &SaleRecord) -> bool {
+    let recomputed: f64 = sale.items.iter().map(|item| item.unit_price * item.quantity as f64).sum();
+    (recomputed - sale.total_amount).abs() <= SALE_TOTAL_TOLERANCE
+}
+
+fn sale_items_profit(items: &[SaleItem]) -> f64 {
+    items
+        .iter()
+        .filter_map(|item| Some((item.unit_price - item.unit_cost?) * item.quantity as f64))
+        .sum()
+}
+
+fn sale_items_tax(items: &[SaleItem]) -> f64 {
+    let tax_rate_percent = CONFIG.with(|config| config.borrow().tax_rate_percent);
+    let taxable: f64 = items
+        .iter()
+        .filter(|item| !item.tax_exempt)
+        .map(|item| item.unit_price * item.quantity as f64)
+        .sum();
+    taxable * tax_rate_percent / 100.0
+}
+
+// Deposits are collected on top of `total_amount` and are never taxed or counted as revenue.
+fn sale_items_deposit(items: &[SaleItem]) -> f64 {
+    items.iter().map(|item| item.deposit * item.quantity as f64).sum()
+}
+
+fn validate_sale_lines(lines: Vec<SaleLine>) -> ApiResult<Vec<SaleLine>> {
+    if lines.is_empty() {
+        return Err(BusinessError::InvalidInput(
+            "A sale must contain at least one item.".to_string(),
+        ));
+    }
+    let max_sale_lines = CONFIG.with(|config| config.borrow().max_sale_lines);
+    if lines.len() as u64 > max_sale_lines {
+        return Err(BusinessError::TooManyLines { max: max_sale_lines });
+    }
+    for line in &lines {
+        if line.quantity == 0 {
+            return Err(BusinessError::ZeroQuantity(format!(
+                "Sale quantity for item with ID {} must be greater than zero.",
+                line.id
+            )));
+        }
+        if let Some(override_price) = line.override_price {
+            if !override_price.is_finite() || override_price <= 0.0 {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Override price for item with ID {} must be positive.",
+                    line.id
+                )));
+            }
+            check_currency_precision(override_price)?;
+        }
+    }
+    Ok(merge_sale_lines(lines))
+}
+
+// `released` holds units that will be put back before the lines are applied, e.g. when amending a sale.
+// The safety stock a sale of `quantity` out of `available` would dip into, unless every line for the item overrides it.
+fn breached_safety_stock(item: &InventoryItem, lines: &[SaleLine], available: u64, quantity: u64) -> Option<u64> {
+    let overridden = lines
+        .iter()
+        .filter(|line| line.id == item.id)
+        .all(|line| line.override_safety_stock.unwrap_or(false));
+    item.safety_stock
+        .filter(|safety_stock| !overridden && available.saturating_sub(quantity) < *safety_stock)
+}
+
+fn check_sale_stock(
+    inventory: &BTreeMap<ItemId, InventoryItem>,
+    lines: &[SaleLine],
+    released: &BTreeMap<ItemId, u64>,
+) -> ApiResult<()> {
+    let requested = merge_lines(lines.iter().map(|line| (line.id, line.quantity)).collect());
+    for (item_id, quantity) in &requested {
+        if let Some(item) = inventory.get(item_id) {
+            if !item.active {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Item: {} is inactive and cannot be sold.",
+                    item.name
+                )));
+            }
+            check_line_serials(item, lines, *quantity)?;
+            let released = released.get(item_id).copied().unwrap_or(0);
+            let available = available_quantity(item).saturating_add(released);
+            if available < *quantity {
+                return Err(BusinessError::InsufficientStock(format!(
+                    "Insufficient stock for item: {}",
+                    item.name
+                )));
+            }
+            if let Some(safety_stock) = breached_safety_stock(item, lines, available, *quantity) {
+                return Err(BusinessError::InsufficientStock(format!(
+                    "Selling {} of item: {} would breach its safety stock of {}.",
+                    quantity, item.name, safety_stock
+                )));
+            }
+        } else {
+            return Err(BusinessError::NotFound(format!("Item with ID {} not found", item_id)));
+        }
+    }
+    Ok(())
+}
+
+fn enforce_line_margins(inventory: &BTreeMap<ItemId, InventoryItem>, lines: &mut [SaleLine]) -> ApiResult<()> {
+    for line in lines {
+        if let (Some(item), Some(price)) = (inventory.get(&line.id), line.override_price) {
+            line.override_price = Some(effective_unit_price(item, item.price, price)?);
+        }
+    }
+    Ok(())
+}
+
+fn check_discount_reasons(inventory: &BTreeMap<ItemId, InventoryItem>, lines: &[SaleLine]) -> ApiResult<()> {
+    for line in lines {
+        if let Some(DiscountReason::Manager(name)) = &line.discount_reason {
+            if name.trim().is_empty() {
+                return Err(BusinessError::InvalidInput(
+                    "Manager discount reason must name the approving manager.".to_string(),
+                ));
+            }
+        }
+        let Some(item) = inventory.get(&line.id) else {
+            continue;
+        };
+        if line.override_price.is_some_and(|price| price < item.price) && line.discount_reason.is_none() {
+            return Err(BusinessError::InvalidInput(format!(
+                "A discount reason is required to sell item: {} below its list price.",
+                item.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_line_serials(item: &InventoryItem, lines: &[SaleLine], quantity: u64) -> ApiResult<()> {
+    let requested: Vec<&String> = lines
+        .iter()
+        .filter(|line| line.id == item.id)
+        .flat_map(|line| line.serials.iter().flatten())
+        .collect();
+    if !item.serialized {
+        if !requested.is_empty() {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} does not track serial numbers.",
+                item.name
+            )));
+        }
+        return Ok(());
+    }
+    if requested.len() as u64 != quantity {
+        return Err(BusinessError::InvalidInput(format!(
+            "Item: {} is serialized; list exactly {} serial numbers to sell.",
+            item.name, quantity
+        )));
+    }
+    SERIALS.with(|serials| {
+        let serials = serials.borrow();
+        let units = serials.get(&item.id).map(Vec::as_slice).unwrap_or_default();
+        for (index, serial) in requested.iter().enumerate() {
+            if requested[..index].contains(serial) {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Serial {} is listed more than once.",
+                    serial
+                )));
+            }
+            match units.iter().find(|unit| &unit.serial == *serial) {
+                Some(unit) if unit.status == SerialStatus::InStock => {}
+                Some(_) => {
+                    return Err(BusinessError::Conflict(format!(
+                        "Serial {} of item: {} has already been sold.",
+                        serial, item.name
+                    )))
+                }
+                None => {
+                    return Err(BusinessError::NotFound(format!(
+                        "Serial {} is not registered for item: {}.",
+                        serial, item.name
+                    )))
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn set_serial_status(item_id: ItemId, serials: &[String], status: SerialStatus, sale_seq: Option<u64>) {
+    SERIALS.with(|units| {
+        if let Some(units) = units.borrow_mut().get_mut(&item_id) {
+            for unit in units.iter_mut().filter(|unit| serials.contains(&unit.serial)) {
+                unit.status = status;
+                unit.sale_seq = sale_seq;
+            }
+        }
+    });
+}
+
+fn apply_sale_lines(
+    inventory: &mut BTreeMap<ItemId, InventoryItem>,
+    lines: Vec<SaleLine>,
+    seq: u64,
+) -> (Vec<SaleItem>, f64) {
+    let priced = price_sale_lines(inventory, &lines);
+    for line in lines {
+        if let Some(item) = inventory.get_mut(&line.id) {
+            record_movement(item, MovementKind::Sale, item.quantity, item.quantity - line.quantity, Some(seq));
+            item.quantity -= line.quantity;
+        }
+        if let Some(sold) = line.serials {
+            set_serial_status(line.id, &sold, SerialStatus::Sold, Some(seq));
+        }
+    }
+    priced
+}
+
+fn price_sale_lines(inventory: &BTreeMap<ItemId, InventoryItem>, lines: &[SaleLine]) -> (Vec<SaleItem>, f64) {
+    let mut sale_items = Vec::new();
+    let mut total_amount = 0.0;
+    for line in lines {
+        if let Some(item) = inventory.get(&line.id) {
+            let unit_price = line.override_price.unwrap_or(item.price);
+            sale_items.push(SaleItem {
+                id: item.id,
+                name: item.name.clone(),
+                quantity: line.quantity,
+                unit_price,
+                list_price: item.price,
+                unit_cost: item.cost_price,
+                category: item.category.clone(),
+                tax_exempt: line.tax_exempt.unwrap_or(item.tax_exempt),
+                serials: line.serials.clone().unwrap_or_default(),
+                deposit: item.deposit.unwrap_or(0.0),
+                deposits_refunded: 0,
+                discount_reason: line.discount_reason.clone().filter(|_| unit_price < item.price),
+            });
+            total_amount += unit_price * line.quantity as f64;
+        }
+    }
+    (sale_items, total_amount)
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn amend_sale(sale_seq: u64, new_lines: Vec<(ItemId, u64)>) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    let requested = validate_sale_lines(new_lines.into_iter().map(SaleLine::from).collect())?;
+    let window = CONFIG.with(|config| config.borrow().sale_amend_window_ns);
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let mut sale = sales
+            .get(sale_seq)
+            .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+        check_sale_settled(&sale)?;
+        if sale.items.iter().any(|item| !item.serials.is_empty()) {
+            return Err(BusinessError::InvalidInput(format!(
+                "Sale {} includes serialized items and cannot be amended.",
+                sale_seq
+            )));
+        }
+        if sale.items.iter().any(|item| item.deposits_refunded > 0) {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has refunded deposits and can no longer be amended.",
+                sale_seq
+            )));
+        }
+        if time().saturating_sub(sale.timestamp) > window {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} is past the amendment window.",
+                sale_seq
+            )));
+        }
+        if RETURNS.with(|returns| returns.borrow().iter().any(|record| record.sale_seq == sale_seq)) {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has returns and can no longer be amended.",
+                sale_seq
+            )));
+        }
+        let mut lines = merge_sale_lines(amended_lines(&sale, &requested));
+
+        INVENTORY.with(|inventory| {
+            let mut inventory = inventory.borrow_mut();
+            let released = merge_lines(sale.items.iter().map(|item| (item.id, item.quantity)).collect());
+            let released: BTreeMap<ItemId, u64> = released
+                .into_iter()
+                .filter(|(id, _)| inventory.contains_key(id))
+                .collect();
+            check_sale_stock(&inventory, &lines, &released)?;
+            enforce_line_margins(&inventory, &mut lines)?;
+            check_discount_reasons(&inventory, &lines)?;
+            check_sale_minimum(&inventory, &lines)?;
+
+            for (id, quantity) in &released {
+                if let Some(item) = inventory.get_mut(id) {
+                    let new_quantity = item.quantity.saturating_add(*quantity);
+                    record_movement(item, MovementKind::Adjustment, item.quantity, new_quantity, Some(sale_seq));
+                    item.quantity = new_quantity;
+                }
+            }
+            let (sale_items, total_amount) = apply_sale_lines(&mut inventory, lines, sale_seq);
+            sale.profit = sale_items_profit(&sale_items);
+            sale.tax_amount = sale_items_tax(&sale_items);
+            sale.deposit_total = sale_items_deposit(&sale_items);
+            sale.items = sale_items;
+            sale.total_amount = total_amount;
+            if let Some(foreign) = sale.foreign_total.as_mut() {
+                foreign.amount = total_amount / foreign.rate_to_base;
+            }
+            sales.insert(&sale);
+            Ok(sale)
+        })
+    })
+}
+
+// Units of an item the original sale already had keep that sale's price, discount reason and tax treatment,
+// filling its lines in order; extra units stay on the terms of the item's last line, new items sell at list price.
+fn amended_lines(sale: &SaleRecord, requested: &[SaleLine]) -> Vec<SaleLine> {
+    let mut lines = Vec::new();
+    for line in requested {
+        let original: Vec<&SaleItem> = sale.items.iter().filter(|item| item.id == line.id).collect();
+        if original.is_empty() {
+            lines.push(line.clone());
+            continue;
+        }
+        let mut remaining = line.quantity;
+        for (index, item) in original.iter().enumerate() {
+            let taken = if index + 1 == original.len() {
+                remaining
+            } else {
+                remaining.min(item.quantity)
+            };
+            if taken == 0 {
+                continue;
+            }
+            remaining -= taken;
+            lines.push(SaleLine {
+                override_price: Some(item.unit_price).filter(|price| *price != item.list_price),
+                tax_exempt: Some(item.tax_exempt),
+                discount_reason: item.discount_reason.clone(),
+                ..SaleLine::from((line.id, taken))
+            });
+        }
+    }
+    lines
+}
+
+// Moves the given quantities into a new sale with the same timestamp and details; a pending sale's reservation is split with it.
+#[update(guard = "caller_is_authenticated")]
+fn split_sale(sale_seq: u64, lines_to_split: Vec<(ItemId, u64)>) -> ApiResult<(u64, u64)> {
+    check_rate_limit()?;
+    if lines_to_split.is_empty() {
+        return Err(BusinessError::InvalidInput(
+            "A split must contain at least one item.".to_string(),
+        ));
+    }
+    if let Some((item_id, _)) = lines_to_split.iter().find(|(_, quantity)| *quantity == 0) {
+        return Err(BusinessError::ZeroQuantity(format!(
+            "Split quantity for item with ID {} must be greater than zero.",
+            item_id
+        )));
+    }
+    let lines_to_split = merge_lines(lines_to_split);
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let mut sale = sales
+            .get(sale_seq)
+            .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+        if sale.status != SaleStatus::Pending {
+            check_sale_settled(&sale)?;
+        } else if sale.voided.is_some() {
+            return Err(BusinessError::Conflict(format!("Sale {} has been voided.", sale_seq)));
+        }
+        if sale.items.iter().any(|item| !item.serials.is_empty()) {
+            return Err(BusinessError::InvalidInput(format!(
+                "Sale {} includes serialized items and cannot be split.",
+                sale_seq
+            )));
+        }
+        if sale.items.iter().any(|item| item.deposits_refunded > 0) {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has refunded deposits and can no longer be split.",
+                sale_seq
+            )));
+        }
+        if RETURNS.with(|returns| returns.borrow().iter().any(|record| record.sale_seq == sale_seq)) {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has returns and can no longer be split.",
+                sale_seq
+            )));
+        }
+        for (item_id, quantity) in &lines_to_split {
+            let sold: u64 = sale.items.iter().filter(|item| item.id == *item_id).map(|item| item.quantity).sum();
+            if *quantity > sold {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Cannot split {} of item with ID {} from sale {}; it only has {}.",
+                    quantity, item_id, sale_seq, sold
+                )));
+            }
+        }
+
+        let mut split_items = Vec::new();
+        for (item_id, quantity) in &lines_to_split {
+            let mut remaining = *quantity;
+            for item in sale.items.iter_mut().filter(|item| item.id == *item_id) {
+                let moved = remaining.min(item.quantity);
+                if moved == 0 {
+                    continue;
+                }
+                item.quantity -= moved;
+                remaining -= moved;
+                split_items.push(SaleItem {
+                    quantity: moved,
+                    ..item.clone()
+                });
+            }
+        }
+        sale.items.retain(|item| item.quantity > 0);
+        if sale.items.is_empty() {
+            return Err(BusinessError::InvalidInput(format!(
+                "Splitting every line would leave sale {} empty.",
+                sale_seq
+            )));
+        }
+
+        let new_seq = next_sale_seq();
+        let mut split = SaleRecord {
+            seq: new_seq,
+            items: split_items,
+            ..sale.clone()
+        };
+        for record in [&mut sale, &mut split] {
+            record.total_amount = record.items.iter().map(|item| item.unit_price * item.quantity as f64).sum();
+            record.profit = sale_items_profit(&record.items);
+            record.tax_amount = sale_items_tax(&record.items);
+            record.deposit_total = sale_items_deposit(&record.items);
+            if let Some(foreign) = record.foreign_total.as_mut() {
+                foreign.amount = record.total_amount / foreign.rate_to_base;
+            }
+        }
+
+        if sale.status == SaleStatus::Pending {
+            RESERVATIONS.with(|reservations| {
+                let mut reservations = reservations.borrow_mut();
+                let Some(held) = reservations
+                    .values_mut()
+                    .find(|reservation| reservation.sale_seq == Some(sale_seq))
+                else {
+                    return;
+                };
+                held.items = merge_lines(sale.items.iter().map(|item| (item.id, item.quantity)).collect());
+                let created_at = held.created_at;
+                let reservation_id = next_reservation_id();
+                reservations.insert(
+                    reservation_id,
+                    Reservation {
+                        id: reservation_id,
+                        items: merge_lines(split.items.iter().map(|item| (item.id, item.quantity)).collect()),
+                        created_at,
+                        sale_seq: Some(new_seq),
+                    },
+                );
+            });
+        }
+
+        sales.insert(&sale);
+        sales.insert(&split);
+        Ok((sale_seq, new_seq))
+    })
+}
+
+fn check_sale_settled(sale: &SaleRecord) -> ApiResult<()> {
+    if sale.voided.is_some() {
+        return Err(BusinessError::Conflict(format!("Sale {} has been voided.", sale.seq)));
+    }
+    match sale.status {
+        SaleStatus::Completed => Ok(()),
+        SaleStatus::Pending => Err(BusinessError::Conflict(format!("Sale {} is still pending.", sale.seq))),
+        SaleStatus::Cancelled => Err(BusinessError::Conflict(format!("Sale {} was cancelled.", sale.seq))),
+    }
+}
+
+fn take_pending_sale(sale_seq: u64) -> ApiResult<(SaleRecord, Option<Reservation>)> {
+    let sale = SALES
+        .with(|sales| sales.borrow().get(sale_seq))
+        .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+    if sale.status != SaleStatus::Pending || sale.voided.is_some() {
+        return Err(BusinessError::Conflict(format!("Sale {} is not pending.", sale_seq)));
+    }
+    let reservation = RESERVATIONS.with(|reservations| {
+        let mut reservations = reservations.borrow_mut();
+        let id = reservations
+            .values()
+            .find(|reservation| reservation.sale_seq == Some(sale_seq))
+            .map(|reservation| reservation.id)?;
+        reservations.remove(&id)
+    });
+    Ok((sale, reservation))
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn confirm_sale(sale_seq: u64) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    let (mut sale, reservation) = take_pending_sale(sale_seq)?;
+    let lines: Vec<SaleLine> = sale
+        .items
+        .iter()
+        .map(|item| SaleLine {
+            id: item.id,
+            quantity: item.quantity,
+            override_price: Some(item.unit_price),
+            tax_exempt: Some(item.tax_exempt),
+            override_safety_stock: Some(true),
+            serials: None,
+            discount_reason: item.discount_reason.clone(),
+        })
+        .collect();
+
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        if let Err(err) = check_sale_stock(&inventory, &lines, &BTreeMap::new()) {
+            if let Some(reservation) = reservation {
+                RESERVATIONS.with(|reservations| reservations.borrow_mut().insert(reservation.id, reservation));
+            }
+            return Err(err);
+        }
+
+        let (sale_items, total_amount) = apply_sale_lines(&mut inventory, lines, sale_seq);
+        sale.timestamp = time();
+        sale.profit = sale_items_profit(&sale_items);
+        sale.tax_amount = sale_items_tax(&sale_items);
+        sale.deposit_total = sale_items_deposit(&sale_items);
+        sale.items = sale_items;
+        sale.total_amount = total_amount;
+        if let Some(foreign) = sale.foreign_total.as_mut() {
+            foreign.amount = total_amount / foreign.rate_to_base;
+        }
+        sale.status = SaleStatus::Completed;
+        SALES.with(|sales| sales.borrow_mut().insert(&sale));
+        Ok(sale)
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn cancel_sale(sale_seq: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    cancel_pending_sale(sale_seq)
+}
+
+fn cancel_pending_sale(sale_seq: u64) -> ApiResult<()> {
+    let (mut sale, _) = take_pending_sale(sale_seq)?;
+    sale.status = SaleStatus::Cancelled;
+    SALES.with(|sales| sales.borrow_mut().insert(&sale));
+    Ok(())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn cancel_stale_pending_sales() -> ApiResult<u64> {
+    check_rate_limit()?;
+    Ok(cancel_expired_pending_sales())
+}
+
+fn cancel_expired_pending_sales() -> u64 {
+    let window = CONFIG.with(|config| config.borrow().pending_sale_window_ns);
+    let now = time();
+    let stale: Vec<u64> = RESERVATIONS.with(|reservations| {
+        reservations
+            .borrow()
+            .values()
+            .filter(|reservation| now.saturating_sub(reservation.created_at) > window)
+            .filter_map(|reservation| reservation.sale_seq)
+            .collect()
+    });
+    stale.into_iter().filter(|sale_seq| cancel_pending_sale(*sale_seq).is_ok()).count() as u64
+}
+
+#[query(guard = "caller_can_read")]
+fn pending_sales() -> Vec<SaleRecord> {
+    SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter_all()
+            .filter(|sale| sale.status == SaleStatus::Pending && sale.voided.is_none())
+            .collect()
+    })
+}
+
+fn merge_sale_lines(lines: Vec<SaleLine>) -> Vec<SaleLine> {
+    let mut merged: Vec<SaleLine> = Vec::with_capacity(lines.len());
+    for line in lines {
+        match merged
+            .iter_mut()
+            .find(|existing| {
+                existing.id == line.id
+                    && existing.override_price == line.override_price
+                    && existing.tax_exempt == line.tax_exempt
+                    && existing.override_safety_stock == line.override_safety_stock
+                    && existing.discount_reason == line.discount_reason
+            })
+        {
+            Some(existing) => {
+                existing.quantity = existing.quantity.saturating_add(line.quantity);
+                if let Some(serials) = line.serials {
+                    existing.serials.get_or_insert_with(Vec::new).extend(serials);
+                }
+            }
+            None => merged.push(line),
+        }
+    }
+    merged
+}
+
+// Batch inputs that repeat an ID are treated as one line with the summed quantity, in first-seen order.
+fn merge_lines(lines: Vec<(ItemId, u64)>) -> Vec<(ItemId, u64)> {
+    let mut merged: Vec<(ItemId, u64)> = Vec::with_capacity(lines.len());
+    for (item_id, quantity) in lines {
+        match merged.iter_mut().find(|(id, _)| *id == item_id) {
+            Some((_, total)) => *total = total.saturating_add(quantity),
+            None => merged.push((item_id, quantity)),
+        }
+    }
+    merged
+}
+
+fn reserved_quantity(item_id: ItemId) -> u64 {
+    RESERVATIONS.with(|reservations| {
+        reservations
+            .borrow()
+            .values()
+            .flat_map(|reservation| reservation.items.iter())
+            .filter(|(id, _)| *id == item_id)
+            .map(|(_, quantity)| *quantity)
+            .sum()
+    })
+}
+
+fn available_quantity(item: &InventoryItem) -> u64 {
+    item.quantity.saturating_sub(reserved_quantity(item.id))
+}
+
+#[query(guard = "caller_can_read")]
+fn check_availability(lines: Vec<(ItemId, u64)>) -> Vec<LineAvailability> {
+    INVENTORY.with(|inventory| {
+        let inventory = inventory.borrow();
+        merge_lines(lines)
+            .into_iter()
+            .map(|(id, requested)| {
+                let available = inventory.get(&id).map_or(0, available_quantity);
+                LineAvailability {
+                    id,
+                    requested,
+                    available,
+                    sufficient: available >= requested,
+                }
+            })
+            .collect()
+    })
+}
+
+// Storefront stock: sellable units minus the buffer held back for walk-in customers.
+fn online_available_quantity(item: &InventoryItem) -> u64 {
+    available_quantity(item).saturating_sub(item.online_buffer.unwrap_or(0))
+}
+
+fn item_availability(item: &InventoryItem) -> ItemAvailability {
+    let reserved = reserved_quantity(item.id);
+    let available = item.quantity.saturating_sub(reserved);
+    ItemAvailability {
+        item: item.clone(),
+        reserved,
+        available,
+        online_available: available.saturating_sub(item.online_buffer.unwrap_or(0)),
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn online_availability(id: ItemId) -> u64 {
+    INVENTORY.with(|inventory| inventory.borrow().get(&id).map_or(0, online_available_quantity))
+}
+
+#[query(guard = "caller_can_read")]
+fn get_item_availability(id: ItemId) -> ApiResult<ItemAvailability> {
+    INVENTORY
+        .with(|inventory| inventory.borrow().get(&id).map(item_availability))
+        .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+}
+
+#[query(guard = "caller_can_read")]
+fn get_inventory_availability() -> Vec<ItemAvailability> {
+    INVENTORY.with(|inventory| inventory.borrow().values().map(item_availability).collect())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn reserve_item(id: ItemId, quantity: u64) -> ApiResult<u64> {
+    check_rate_limit()?;
+    if quantity == 0 {
+        return Err(BusinessError::ZeroQuantity(
+            "Reservation quantity must be greater than zero.".to_string(),
+        ));
+    }
+
+    INVENTORY.with(|inventory| {
+        let inventory = inventory.borrow();
+        let item = inventory
+            .get(&id)
+            .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+        if !item.active {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} is inactive and cannot be reserved.",
+                item.name
+            )));
+        }
+        if item.serialized {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} is serialized and cannot be reserved.",
+                item.name
+            )));
+        }
+        if available_quantity(item) < quantity {
+            return Err(BusinessError::InsufficientStock(format!(
+                "Insufficient stock for item: {}",
+                item.name
+            )));
+        }
+
+        let reservation_id = next_reservation_id();
+        RESERVATIONS.with(|reservations| {
+            reservations.borrow_mut().insert(
+                reservation_id,
+                Reservation {
+                    id: reservation_id,
+                    items: vec![(id, quantity)],
+                    created_at: time(),
+                    sale_seq: None,
+                },
+            )
+        });
+        Ok(reservation_id)
+    })
+}
+
+// Every line is checked before anything is held, so a short line leaves no partial reservation behind.
+#[update(guard = "caller_is_authenticated")]
+fn reserve_cart(lines: Vec<(ItemId, u64)>) -> ApiResult<u64> {
+    check_rate_limit()?;
+    if lines.is_empty() {
+        return Err(BusinessError::InvalidInput(
+            "A cart must contain at least one item.".to_string(),
+        ));
+    }
+    if let Some((item_id, _)) = lines.iter().find(|(_, quantity)| *quantity == 0) {
+        return Err(BusinessError::ZeroQuantity(format!(
+            "Reservation quantity for item with ID {} must be greater than zero.",
+            item_id
+        )));
+    }
+    let lines = merge_lines(lines);
+    let max_sale_lines = CONFIG.with(|config| config.borrow().max_sale_lines);
+    if lines.len() as u64 > max_sale_lines {
+        return Err(BusinessError::TooManyLines { max: max_sale_lines });
+    }
+
+    INVENTORY.with(|inventory| {
+        let inventory = inventory.borrow();
+        for (id, quantity) in &lines {
+            let item = inventory
+                .get(id)
+                .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+            if !item.active {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Item: {} is inactive and cannot be reserved.",
+                    item.name
+                )));
+            }
+            if item.serialized {
+                return Err(BusinessError::InvalidInput(format!(
+                    "Item: {} is serialized and cannot be reserved.",
+                    item.name
+                )));
+            }
+            if available_quantity(item) < *quantity {
+                return Err(BusinessError::InsufficientStock(format!(
+                    "Insufficient stock for item: {}",
+                    item.name
+                )));
+            }
+        }
+
+        let reservation_id = next_reservation_id();
+        RESERVATIONS.with(|reservations| {
+            reservations.borrow_mut().insert(
+                reservation_id,
+                Reservation {
+                    id: reservation_id,
+                    items: lines,
+                    created_at: time(),
+                    sale_seq: None,
+                },
+            )
+        });
+        Ok(reservation_id)
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn release_reservation(reservation_id: u64) -> ApiResult<()> {
+    check_rate_limit()?;
+    check_reservation_unlinked(reservation_id)?;
+    RESERVATIONS.with(|reservations| {
+        if reservations.borrow_mut().remove(&reservation_id).is_some() {
+            Ok(())
+        } else {
+            Err(BusinessError::NotFound(format!(
+                "Reservation {} not found.",
+                reservation_id
+            )))
+        }
+    })
+}
+
+fn check_reservation_unlinked(reservation_id: u64) -> ApiResult<()> {
+    let sale_seq = RESERVATIONS.with(|reservations| {
+        reservations
+            .borrow()
+            .get(&reservation_id)
+            .and_then(|reservation| reservation.sale_seq)
+    });
+    match sale_seq {
+        Some(sale_seq) => Err(BusinessError::Conflict(format!(
+            "Reservation {} holds pending sale {}; confirm or cancel the sale instead.",
+            reservation_id, sale_seq
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn fulfill_reservation(reservation_id: u64) -> ApiResult<SaleRecord> {
+    check_rate_limit()?;
+    check_reservation_unlinked(reservation_id)?;
+    let reservation = RESERVATIONS
+        .with(|reservations| reservations.borrow_mut().remove(&reservation_id))
+        .ok_or_else(|| BusinessError::NotFound(format!("Reservation {} not found.", reservation_id)))?;
+
+    process_sale(reservation.items.iter().copied().map(SaleLine::from).collect(), SaleOptions::default()).inspect_err(|_| {
+        RESERVATIONS.with(|reservations| reservations.borrow_mut().insert(reservation_id, reservation));
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn expire_stale_reservations() -> ApiResult<u64> {
+    check_rate_limit()?;
+    Ok(expire_old_reservations())
+}
+
+fn expire_old_reservations() -> u64 {
+    let ttl = CONFIG.with(|config| config.borrow().reservation_ttl_ns);
+    let now = time();
+    RESERVATIONS.with(|reservations| {
+        let mut reservations = reservations.borrow_mut();
+        let before = reservations.len();
+        reservations.retain(|_, reservation| {
+            reservation.sale_seq.is_some() || now.saturating_sub(reservation.created_at) <= ttl
+        });
+        (before - reservations.len()) as u64
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn get_reservations() -> Vec<Reservation> {
+    RESERVATIONS.with(|reservations| reservations.borrow().values().cloned().collect())
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn return_sale_items(sale_seq: u64, items: Vec<(ItemId, u64)>, reason: ReturnReason) -> ApiResult<ReturnRecord> {
+    check_rate_limit()?;
+    if items.is_empty() {
+        return Err(BusinessError::InvalidInput(
+            "A return must contain at least one item.".to_string(),
+        ));
+    }
+    if let Some((item_id, _)) = items.iter().find(|(_, quantity)| *quantity == 0) {
+        return Err(BusinessError::ZeroQuantity(format!(
+            "Return quantity for item with ID {} must be greater than zero.",
+            item_id
+        )));
+    }
+    if let ReturnReason::Other(description) = &reason {
+        if description.trim().is_empty() {
+            return Err(BusinessError::InvalidInput(
+                "Return reason cannot be empty.".to_string(),
+            ));
+        }
+    }
+    let items = merge_lines(items);
+
+    let sale = SALES
+        .with(|sales| sales.borrow().get(sale_seq))
+        .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+    check_sale_settled(&sale)?;
+
+    let mut returned: BTreeMap<ItemId, u64> = BTreeMap::new();
+    RETURNS.with(|returns| {
+        for record in returns.borrow().iter().filter(|record| record.sale_seq == sale_seq) {
+            for item in &record.items {
+                *returned.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+
+    let mut return_items = Vec::new();
+    let mut refund_amount = 0.0;
+    for (item_id, quantity) in items {
+        let sale_item = sale.items.iter().find(|item| item.id == item_id).ok_or_else(|| {
+            BusinessError::InvalidInput(format!("Item with ID {} is not part of sale {}.", item_id, sale_seq))
+        })?;
+        if !sale_item.serials.is_empty() {
+            return Err(BusinessError::InvalidInput(format!(
+                "Item: {} is serialized and cannot be returned by quantity.",
+                sale_item.name
+            )));
+        }
+        let sold: u64 = sale.items.iter().filter(|item| item.id == item_id).map(|item| item.quantity).sum();
+        let paid: f64 = sale
+            .items
+            .iter()
+            .filter(|item| item.id == item_id)
+            .map(|item| item.unit_price * item.quantity as f64)
+            .sum();
+        let already_returned = returned.entry(item_id).or_insert(0);
+        if *already_returned + quantity > sold {
+            return Err(BusinessError::RefundExceedsPayment(format!(
+                "Cannot return {} of item: {}; only {} remain returnable.",
+                quantity,
+                sale_item.name,
+                sold - *already_returned
+            )));
+        }
+        *already_returned += quantity;
+
+        // Lines sold at different prices are refunded at their average, i.e. what was actually paid per unit.
+        let paid_per_unit = paid / sold as f64;
+        return_items.push(SaleItem {
+            quantity,
+            unit_price: paid_per_unit,
+            ..sale_item.clone()
+        });
+        refund_amount += paid_per_unit * quantity as f64;
+    }
+    let already_refunded: f64 = RETURNS.with(|returns| {
+        returns
+            .borrow()
+            .iter()
+            .filter(|record| record.sale_seq == sale_seq)
+            .map(|record| record.refund_amount)
+            .sum()
+    });
+    let refund_amount = refund_amount.min((sale.total_amount - already_refunded).max(0.0));
+
+    let return_id = next_return_id();
+    INVENTORY.with(|inventory| {
+        let mut inventory = inventory.borrow_mut();
+        for item in &return_items {
+            if let Some(stock) = inventory.get_mut(&item.id) {
+                let new_quantity = stock.quantity.saturating_add(item.quantity);
+                record_movement(stock, MovementKind::Return, stock.quantity, new_quantity, Some(return_id));
+                stock.quantity = new_quantity;
+            }
+        }
+    });
+
+    let returned_profit = sale_items_profit(&return_items);
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        if let Some(mut sale) = sales.get(sale_seq) {
+            sale.profit -= returned_profit;
+            sales.insert(&sale);
+        }
+    });
+
+    let return_record = ReturnRecord {
+        id: return_id,
+        sale_seq,
+        timestamp: time(),
+        items: return_items,
+        refund_amount,
+        reason,
+    };
+    RETURNS.with(|returns| returns.borrow_mut().push(return_record.clone()));
+    Ok(return_record)
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn refund_deposit(sale_seq: u64, item_id: ItemId, quantity: u64) -> ApiResult<f64> {
+    check_rate_limit()?;
+    if quantity == 0 {
+        return Err(BusinessError::ZeroQuantity(
+            "Refund quantity must be greater than zero.".to_string(),
+        ));
+    }
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let mut sale = sales
+            .get(sale_seq)
+            .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+        check_sale_settled(&sale)?;
+        let refundable: u64 = sale
+            .items
+            .iter()
+            .filter(|item| item.id == item_id && item.deposit > 0.0)
+            .map(|item| item.quantity - item.deposits_refunded)
+            .sum();
+        if quantity > refundable {
+            return Err(BusinessError::RefundExceedsPayment(format!(
+                "Cannot refund {} deposits for item with ID {} on sale {}; only {} are outstanding.",
+                quantity, item_id, sale_seq, refundable
+            )));
+        }
+
+        let mut remaining = quantity;
+        let mut refund = 0.0;
+        for item in sale.items.iter_mut().filter(|item| item.id == item_id && item.deposit > 0.0) {
+            let units = remaining.min(item.quantity - item.deposits_refunded);
+            item.deposits_refunded += units;
+            refund += item.deposit * units as f64;
+            remaining -= units;
+        }
+        sales.insert(&sale);
+        Ok(refund)
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn outstanding_deposits() -> f64 {
+    SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .flat_map(|sale| sale.items)
+            .map(|item| item.deposit * (item.quantity - item.deposits_refunded) as f64)
+            .sum()
+    })
+}
+
+#[update(guard = "caller_is_authenticated")]
+fn void_sale(sale_seq: u64, reason: String) -> ApiResult<()> {
+    check_rate_limit()?;
+    let reason = reason.trim().to_string();
+    if reason.is_empty() {
+        return Err(BusinessError::InvalidInput("Void reason cannot be empty.".to_string()));
+    }
+    if reason.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(BusinessError::InvalidInput(format!(
+            "Void reason cannot exceed {} characters.",
+            MAX_DESCRIPTION_LENGTH
+        )));
+    }
+
+    SALES.with(|sales| {
+        let mut sales = sales.borrow_mut();
+        let mut sale = sales
+            .get(sale_seq)
+            .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", sale_seq)))?;
+        if sale.voided.is_some() {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has already been voided.",
+                sale_seq
+            )));
+        }
+        check_sale_settled(&sale)?;
+        if RETURNS.with(|returns| returns.borrow().iter().any(|record| record.sale_seq == sale_seq)) {
+            return Err(BusinessError::Conflict(format!(
+                "Sale {} has returns and can no longer be voided.",
+                sale_seq
+            )));
+        }
+
+        INVENTORY.with(|inventory| {
+            let mut inventory = inventory.borrow_mut();
+            for (id, quantity) in merge_lines(sale.items.iter().map(|item| (item.id, item.quantity)).collect()) {
+                if let Some(item) = inventory.get_mut(&id) {
+                    let new_quantity = item.quantity.saturating_add(quantity);
+                    record_movement(item, MovementKind::Void, item.quantity, new_quantity, Some(sale_seq));
+                    item.quantity = new_quantity;
+                }
+            }
+        });
+        for item in sale.items.iter().filter(|item| !item.serials.is_empty()) {
+            set_serial_status(item.id, &item.serials, SerialStatus::InStock, None);
+        }
+
+        sale.voided = Some(SaleVoid {
+            timestamp: time(),
+            reason,
+        });
+        sales.insert(&sale);
+        Ok(())
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn reconcile_item(id: ItemId) -> ApiResult<ReconcileReport> {
+    let recorded_quantity = INVENTORY
+        .with(|inventory| inventory.borrow().get(&id).map(|item| item.quantity))
+        .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+    let expected: i128 = MOVEMENTS.with(|movements| {
+        movements
+            .borrow()
+            .iter()
+            .filter(|movement| movement.item_id == id)
+            .map(|movement| movement.delta as i128)
+            .sum()
+    });
+    let expected_quantity = expected.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+    let discrepancy = (recorded_quantity as i128 - expected).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+
+    Ok(ReconcileReport {
+        id,
+        recorded_quantity,
+        expected_quantity,
+        discrepancy,
+        consistent: discrepancy == 0,
+    })
+}
+
+// Balances run over the item's whole movement history, so entries inside the window show the true quantity after each event.
+#[query(guard = "caller_can_read")]
+fn stock_ledger(item_id: ItemId, start: u64, end: u64) -> ApiResult<Vec<StockLedgerEntry>> {
+    validate_window(start, end)?;
+    MOVEMENTS.with(|movements| {
+        let mut balance: i128 = 0;
+        let mut ledger = Vec::new();
+        for movement in movements.borrow().iter().filter(|movement| movement.item_id == item_id) {
+            balance += movement.delta as i128;
+            if movement.timestamp < start || movement.timestamp > end {
+                continue;
+            }
+            if ledger.len() == MAX_LEDGER_ENTRIES {
+                return Err(BusinessError::InvalidInput(format!(
+                    "The window holds more than {} ledger lines; narrow it and try again.",
+                    MAX_LEDGER_ENTRIES
+                )));
+            }
+            ledger.push(StockLedgerEntry {
+                timestamp: movement.timestamp,
+                kind: movement.kind,
+                delta: movement.delta,
+                balance_after: balance.clamp(0, u64::MAX as i128) as u64,
+                ref_id: movement.ref_id,
+            });
+        }
+        Ok(ledger)
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn get_returns() -> Vec<ReturnRecord> {
+    RETURNS.with(|returns| returns.borrow().clone())
+}
+
+#[query(guard = "caller_can_read")]
+fn returns_by_reason(start: u64, end: u64) -> ApiResult<Vec<(ReturnReason, u64, f64)>> {
+    validate_window(start, end)?;
+    let mut breakdown: Vec<(ReturnReason, u64, f64)> = Vec::new();
+
+    RETURNS.with(|returns| {
+        for record in returns.borrow().iter() {
+            if record.timestamp < start || record.timestamp > end {
+                continue;
+            }
+            match breakdown.iter_mut().find(|(reason, _, _)| *reason == record.reason) {
+                Some((_, count, value)) => {
+                    *count += 1;
+                    *value += record.refund_amount;
+                }
+                None => breakdown.push((record.reason.clone(), 1, record.refund_amount)),
+            }
+        }
+    });
+
+    breakdown.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.2.total_cmp(&a.2)));
+    Ok(breakdown)
+}
+
+#[query(guard = "caller_can_read")]
+fn get_inventory() -> Vec<InventoryItem> {
+    INVENTORY.with(|inventory| inventory.borrow().values().cloned().collect())
+}
+
+// Bumped on every item change, including stock movements from sales, so caches can poll for staleness.
+#[query(guard = "caller_can_read")]
+fn get_catalog_version() -> u64 {
+    CATALOG_VERSION.load(Ordering::Relaxed)
+}
+
+#[query(guard = "caller_can_read")]
+fn get_item_details(id: ItemId) -> ApiResult<InventoryItem> {
+    INVENTORY
+        .with(|inventory| inventory.borrow().get(&id).cloned())
+        .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))
+}
+
+#[query(guard = "caller_can_read")]
+fn get_variants(name: String) -> Vec<InventoryItem> {
+    let name = normalize_name(&name).to_lowercase();
+    let mut variants: Vec<InventoryItem> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.name.to_lowercase() == name)
+            .cloned()
+            .collect()
+    });
+    variants.sort_by(|a, b| a.variant.cmp(&b.variant));
+    variants
+}
+
+#[query(guard = "caller_can_read")]
+fn inventory_count() -> u64 {
+    INVENTORY.with(|inventory| inventory.borrow().len() as u64)
+}
+
+#[query(guard = "caller_can_read")]
+fn get_featured() -> Vec<InventoryItem> {
+    let featured = CONFIG.with(|config| config.borrow().featured_items.clone());
+    INVENTORY.with(|inventory| {
+        let inventory = inventory.borrow();
+        featured
+            .iter()
+            .filter_map(|id| inventory.get(id))
+            .filter(|item| online_available_quantity(item) > 0)
+            .cloned()
+            .collect()
+    })
+}
+
+// The seed comes from `raw_rand` once and the day's pick is cached, so the deal holds steady until the next local day.
+fn refresh_daily_deal() {
+    if DEAL_SEED.with(Cell::get).is_none() {
+        if !DEAL_SEED_REQUESTED.with(|requested| requested.replace(true)) {
+            ic_cdk::spawn(async {
+                match raw_rand().await {
+                    Ok((bytes,)) if bytes.len() >= 8 => {
+                        let seed = u64::from_le_bytes(bytes[..8].try_into().expect("slice has 8 bytes"));
+                        DEAL_SEED.with(|cell| cell.set(Some(seed)));
+                    }
+                    _ => DEAL_SEED_REQUESTED.with(|requested| requested.set(false)),
+                }
+            });
+        }
+        return;
+    }
+    let today = local_day_number(time());
+    if DAILY_DEAL.with(Cell::get).is_some_and(|(day, _)| day == today) {
+        return;
+    }
+    let pick = pick_daily_deal(today);
+    DAILY_DEAL.with(|deal| deal.set(Some((today, pick))));
+}
+
+// Weight grows with how far stock sits above the reorder point and how long the item has gone unsold.
+fn pick_daily_deal(day: i64) -> Option<ItemId> {
+    let seed = DEAL_SEED.with(Cell::get)?;
+    let now = time();
+    let default_reorder_point = CONFIG.with(|config| config.borrow().default_reorder_point);
+    let last_sold = last_sold_times();
+    let candidates: Vec<(ItemId, f64)> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.active)
+            .filter_map(|item| {
+                let reorder_point = effective_reorder_point(item, default_reorder_point);
+                let available = online_available_quantity(item);
+                if available == 0 || available <= reorder_point {
+                    return None;
+                }
+                let overstock = (available as f64 / reorder_point.max(1) as f64).min(DEAL_MAX_WEIGHT_FACTOR);
+                let idle_since = last_sold.get(&item.id).copied().unwrap_or(item.created_at);
+                let idle_months = (now.saturating_sub(idle_since) as f64 / NANOS_PER_DAY as f64 / 30.0).min(DEAL_MAX_WEIGHT_FACTOR);
+                Some((item.id, 1.0 + overstock + idle_months))
+            })
+            .collect()
+    });
+
+    let total_weight: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+    let mut target = deal_seed_fraction(seed, day) * total_weight;
+    for (id, weight) in &candidates {
+        if target < *weight {
+            return Some(*id);
+        }
+        target -= weight;
+    }
+    candidates.last().map(|(id, _)| *id)
+}
+
+// SplitMix64 finalizer: spreads the seed and day into a uniform fraction in [0, 1).
+fn deal_seed_fraction(seed: u64, day: i64) -> f64 {
+    let mut x = seed ^ (day as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[query(guard = "caller_can_read")]
+fn deal_of_the_day() -> Option<InventoryItem> {
+    let today = local_day_number(time());
+    let id = match DAILY_DEAL.with(Cell::get) {
+        Some((day, pick)) if day == today => pick?,
+        _ => pick_daily_deal(today)?,
+    };
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .get(&id)
+            .filter(|item| item.active && online_available_quantity(item) > 0)
+            .cloned()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn get_item_count() -> u64 {
+    inventory_count()
+}
+
+#[query(guard = "caller_can_read")]
+fn get_sale_count() -> u64 {
+    COMPLETED_SALES.load(Ordering::Relaxed)
+}
+
+#[query(guard = "caller_can_read")]
+fn total_units() -> u64 {
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .fold(0u64, |total, item| total.saturating_add(item.quantity))
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn out_of_stock_count() -> u64 {
+    INVENTORY.with(|inventory| inventory.borrow().values().filter(|item| item.quantity == 0).count() as u64)
+}
+
+#[query(guard = "caller_can_read")]
+fn search_item_by_name(name: String) -> Vec<InventoryItem> {
+    let name_lower = name.to_lowercase();
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.name.to_lowercase().contains(&name_lower))
+            .cloned()
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn search_items(text: String) -> Vec<InventoryItem> {
+    let text_lower = text.to_lowercase();
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| matches_text(item, &text_lower))
+            .cloned()
+            .collect()
+    })
+}
+
+fn text_match_score(item: &InventoryItem, text_lower: &str) -> f64 {
+    let name_lower = item.name.to_lowercase();
+    if name_lower == text_lower {
+        1.0
+    } else if name_lower.starts_with(text_lower) {
+        0.75
+    } else if name_lower.contains(text_lower) {
+        0.5
+    } else if matches_text(item, text_lower) {
+        0.25
+    } else {
+        0.0
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn search_ranked(query: String, limit: usize) -> Vec<InventoryItem> {
+    let text_lower = query.trim().to_lowercase();
+    if text_lower.is_empty() || limit == 0 {
+        return Vec::new();
+    }
+
+    let since = time().saturating_sub(SEARCH_POPULARITY_WINDOW_DAYS * 24 * 60 * 60 * NANOS_PER_SECOND);
+    let mut recent_units: HashMap<ItemId, u64> = HashMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= since) {
+            for item in &sale.items {
+                *recent_units.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+
+    let matches: Vec<(InventoryItem, f64)> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.active)
+            .filter_map(|item| {
+                let score = text_match_score(item, &text_lower);
+                (score > 0.0).then(|| (item.clone(), score))
+            })
+            .collect()
+    });
+    let max_units = matches
+        .iter()
+        .filter_map(|(item, _)| recent_units.get(&item.id).copied())
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+
+    let mut ranked: Vec<(InventoryItem, f64, bool)> = matches
+        .into_iter()
+        .map(|(item, text_score)| {
+            let popularity = recent_units.get(&item.id).copied().unwrap_or(0) as f64 / max_units;
+            let score = SEARCH_TEXT_WEIGHT * text_score + SEARCH_POPULARITY_WEIGHT * popularity;
+            let in_stock = online_available_quantity(&item) > 0;
+            (item, score, in_stock)
+        })
+        .collect();
+    ranked.sort_by(|(a, a_score, a_in_stock), (b, b_score, b_in_stock)| {
+        b_in_stock
+            .cmp(a_in_stock)
+            .then(b_score.total_cmp(a_score))
+            .then(a.id.cmp(&b.id))
+    });
+    ranked
+        .into_iter()
+        .take(limit.min(MAX_PAGE_SIZE as usize))
+        .map(|(item, _, _)| item)
+        .collect()
+}
+
+fn matches_text(item: &InventoryItem, text_lower: &str) -> bool {
+    item.name.to_lowercase().contains(text_lower)
+        || item
+            .description
+            .as_ref()
+            .is_some_and(|description| description.to_lowercase().contains(text_lower))
+}
+
+#[query(guard = "caller_can_read")]
+fn search(params: SearchParams) -> ApiResult<SearchPage> {
+    let criteria = FilterCriteria::new(&InventoryFilter {
+        text: params.text.clone(),
+        category: params.category.clone(),
+        tags: params.tags.clone(),
+        in_stock_only: params.in_stock_only,
+        include_inactive: params.include_inactive.unwrap_or(false),
+    })?;
+    let limit = params.limit.min(MAX_PAGE_SIZE);
+
+    let mut matches: Vec<InventoryItem> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| criteria.matches(item))
+            .cloned()
+            .collect()
+    });
+
+    matches.sort_by(|a, b| {
+        let ordering = match params.sort {
+            SortField::Id => a.id.cmp(&b.id),
+            SortField::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortField::Price => a.price.total_cmp(&b.price),
+            SortField::Quantity => a.quantity.cmp(&b.quantity),
+            SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+        }
+        .then_with(|| a.id.cmp(&b.id));
+        if params.ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+
+    let total = matches.len() as u64;
+    let items = matches
+        .into_iter()
+        .skip(params.offset.min(total) as usize)
+        .take(limit as usize)
+        .collect();
+
+    Ok(SearchPage {
+        items,
+        total,
+        offset: params.offset,
+        limit,
+    })
+}
+
+// Storefront listing: active items that can still be sold online, by name.
+#[query(guard = "caller_can_read")]
+fn get_available_inventory(offset: u64, limit: u64) -> SearchPage {
+    let limit = limit.min(MAX_PAGE_SIZE);
+    let mut available: Vec<InventoryItem> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.active && online_available_quantity(item) > 0)
+            .cloned()
+            .collect()
+    });
+    available.sort_by(|a, b| {
+        a.name
+            .to_lowercase()
+            .cmp(&b.name.to_lowercase())
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    let total = available.len() as u64;
+    let items = available
+        .into_iter()
+        .skip(offset.min(total) as usize)
+        .take(limit as usize)
+        .collect();
+    SearchPage {
+        items,
+        total,
+        offset,
+        limit,
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn get_sales() -> Vec<SaleRecord> {
+    SALES.with(|sales| sales.borrow().iter_all().collect())
+}
+
+#[query(guard = "caller_can_read")]
+fn get_sale(seq: u64) -> ApiResult<SaleRecord> {
+    SALES
+        .with(|sales| sales.borrow().get(seq))
+        .ok_or_else(|| BusinessError::NotFound(format!("Sale {} not found.", seq)))
+}
+
+#[query(guard = "caller_can_read")]
+fn export_sales_page(after_seq: Option<u64>, limit: u64) -> ApiResult<SalesExportPage> {
+    let limit = limit.clamp(1, MAX_EXPORT_PAGE_SIZE) as usize;
+    SALES.with(|sales| {
+        let sales = sales.borrow();
+        let mut remaining = sales.after(after_seq);
+        let page: Vec<SaleRecord> = remaining.by_ref().take(limit).collect();
+        let json = serde_json::to_string(&page)
+            .map_err(|e| BusinessError::InvalidInput(format!("Failed to serialize sales: {}", e)))?;
+        let next_cursor = if remaining.next().is_some() {
+            page.last().map(|sale| sale.seq)
+        } else {
+            None
+        };
+
+        Ok(SalesExportPage {
+            json,
+            count: page.len() as u64,
+            next_cursor,
+        })
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn sales_ledger(start: u64, end: u64) -> ApiResult<SalesLedger> {
+    validate_window(start, end)?;
+    let mut ledger = SalesLedger {
+        entries: Vec::new(),
+        total_sales: 0.0,
+        total_tax: 0.0,
+        total_discount: 0.0,
+        count: 0,
+    };
+
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            if ledger.entries.len() + sale.items.len() > MAX_LEDGER_ENTRIES {
+                return Err(BusinessError::InvalidInput(format!(
+                    "The window holds more than {} ledger lines; narrow it and try again.",
+                    MAX_LEDGER_ENTRIES
+                )));
+            }
+            ledger.count += 1;
+            ledger.total_sales += sale.total_amount;
+            ledger.total_tax += sale.tax_amount;
+            for item in sale.items {
+                ledger.total_discount += ((item.list_price - item.unit_price) * item.quantity as f64).max(0.0);
+                ledger.entries.push(LedgerEntry {
+                    sale_id: sale.seq,
+                    timestamp: sale.timestamp,
+                    item_id: item.id,
+                    line_total: item.unit_price * item.quantity as f64,
+                    item_name: item.name,
+                    quantity: item.quantity,
+                    unit_price: item.unit_price,
+                });
+            }
+        }
+        Ok(())
+    })?;
+
+    ledger.total_sales = round_currency(ledger.total_sales);
+    ledger.total_tax = round_currency(ledger.total_tax);
+    ledger.total_discount = round_currency(ledger.total_discount);
+    Ok(ledger)
+}
+
+#[query(guard = "caller_can_read")]
+fn tax_liability(start: u64, end: u64) -> ApiResult<TaxLiability> {
+    validate_window(start, end)?;
+    let mut liability = TaxLiability {
+        taxable_sales: 0.0,
+        tax_collected: 0.0,
+        exempt_sales: 0.0,
+    };
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            liability.tax_collected += sale.tax_amount;
+            for item in &sale.items {
+                let line_total = item.unit_price * item.quantity as f64;
+                if item.tax_exempt {
+                    liability.exempt_sales += line_total;
+                } else {
+                    liability.taxable_sales += line_total;
+                }
+            }
+        }
+    });
+    liability.taxable_sales = round_currency(liability.taxable_sales);
+    liability.tax_collected = round_currency(liability.tax_collected);
+    liability.exempt_sales = round_currency(liability.exempt_sales);
+    Ok(liability)
+}
+
+#[query(guard = "caller_can_read")]
+fn verify_sale_totals() -> Vec<u64> {
+    SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter_all()
+            .filter(|sale| !sale_total_matches(sale))
+            .map(|sale| sale.seq)
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn find_sales_by_note(substring: String) -> Vec<SaleRecord> {
+    let substring = substring.trim().to_lowercase();
+    if substring.is_empty() {
+        return Vec::new();
+    }
+    SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter_all()
+            .filter(|sale| {
+                sale.note
+                    .as_ref()
+                    .is_some_and(|note| note.to_lowercase().contains(&substring))
+            })
+            .take(MAX_PAGE_SIZE as usize)
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn recent_sales(limit: usize) -> Vec<SaleRecord> {
+    SALES.with(|sales| {
+        sales.borrow().latest(limit.min(MAX_PAGE_SIZE as usize))
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn sales_today() -> Vec<SaleRecord> {
+    let (start, end) = day_bounds(time());
+    SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= start && sale.timestamp < end)
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn revenue_today() -> f64 {
+    sales_today().iter().map(|sale| sale.total_amount).sum()
+}
+
+#[query(guard = "caller_can_read")]
+fn sales_heatmap(month_start: u64) -> Vec<DayRevenue> {
+    let (year, month, _) = civil_from_days(business_day_number(month_start));
+    let first_day = days_from_civil(year, month, 1);
+    let last_day = first_day + days_in_month(year, month) as i64 - 1;
+    let mut heatmap: Vec<DayRevenue> = (1..=days_in_month(year, month))
+        .map(|day| DayRevenue {
+            day,
+            revenue: 0.0,
+            num_sales: 0,
+        })
+        .collect();
+
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            let day = business_day_number(sale.timestamp);
+            if (first_day..=last_day).contains(&day) {
+                let entry = &mut heatmap[(day - first_day) as usize];
+                entry.revenue += sale.total_amount;
+                entry.num_sales += 1;
+            }
+        }
+    });
+    heatmap
+}
+
+#[query(guard = "caller_can_read")]
+fn compare_items(a: ItemId, b: ItemId, start: u64, end: u64) -> ApiResult<ItemComparison> {
+    validate_window(start, end)?;
+    let window_stats = |id: ItemId| {
+        let mut stats = ItemWindowStats {
+            id,
+            name: INVENTORY.with(|inventory| inventory.borrow().get(&id).map(|item| item.name.clone())),
+            units_sold: 0,
+            revenue: 0.0,
+            profit: 0.0,
+        };
+        SALES.with(|sales| {
+            for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+                for item in sale.items.iter().filter(|item| item.id == id) {
+                    stats.units_sold += item.quantity;
+                    stats.revenue += item.unit_price * item.quantity as f64;
+                    if let Some(unit_cost) = item.unit_cost {
+                        stats.profit += (item.unit_price - unit_cost) * item.quantity as f64;
+                    }
+                    if stats.name.is_none() {
+                        stats.name = Some(item.name.clone());
+                    }
+                }
+            }
+        });
+        stats.revenue = round_currency(stats.revenue);
+        stats.profit = round_currency(stats.profit);
+        stats
+    };
+
+    Ok(ItemComparison {
+        a: window_stats(a),
+        b: window_stats(b),
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn item_sales_series(id: ItemId, granularity: Granularity, start: u64, end: u64) -> ApiResult<Vec<(u64, u64)>> {
+    validate_window(start, end)?;
+    let first = period_index(start, granularity);
+    let last = period_index(end, granularity);
+    if last - first + 1 > MAX_SERIES_BUCKETS {
+        return Err(BusinessError::InvalidInput(format!(
+            "A series cannot span more than {} periods.",
+            MAX_SERIES_BUCKETS
+        )));
+    }
+
+    let mut units = vec![0u64; (last - first + 1) as usize];
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            let sold: u64 = sale.items.iter().filter(|item| item.id == id).map(|item| item.quantity).sum();
+            if sold > 0 {
+                let bucket = (period_index(sale.timestamp, granularity) - first) as usize;
+                units[bucket] = units[bucket].saturating_add(sold);
+            }
+        }
+    });
+    Ok(units
+        .into_iter()
+        .enumerate()
+        .map(|(offset, units)| (period_start(first + offset as i64, granularity), units))
+        .collect())
+}
+
+#[query(guard = "caller_can_read")]
+fn detect_anomalies(lookback_ns: u64) -> Vec<SaleAnomaly> {
+    let since = time().saturating_sub(lookback_ns);
+    let recent: Vec<SaleRecord> =
+        SALES.with(|sales| sales.borrow().iter().filter(|sale| sale.timestamp >= since).collect());
+    let sum: f64 = recent.iter().map(|sale| sale.total_amount).sum();
+    let sum_of_squares: f64 = recent.iter().map(|sale| sale.total_amount * sale.total_amount).sum();
+    let baseline_size = recent.len().saturating_sub(1);
+
+    recent
+        .into_iter()
+        .filter_map(|sale| {
+            let mut reasons = Vec::new();
+
+            // Each sale is scored against the others so an outlier cannot inflate its own baseline.
+            if baseline_size >= ANOMALY_MIN_BASELINE_SALES {
+                let n = baseline_size as f64;
+                let mean = (sum - sale.total_amount) / n;
+                let variance = (sum_of_squares - sale.total_amount * sale.total_amount) / n - mean * mean;
+                let std_dev = variance.max(0.0).sqrt();
+                if std_dev > 0.0 {
+                    let z_score = (sale.total_amount - mean) / std_dev;
+                    if z_score > ANOMALY_Z_SCORE {
+                        reasons.push(AnomalyReason::HighTotal { z_score });
+                    }
+                }
+            }
+
+            let list_total: f64 = sale.items.iter().map(|item| item.list_price * item.quantity as f64).sum();
+            if list_total > 0.0 {
+                let discount_percent = (1.0 - sale.total_amount / list_total) * 100.0;
+                if discount_percent >= ANOMALY_DISCOUNT_PERCENT {
+                    reasons.push(AnomalyReason::DeepDiscount { discount_percent });
+                }
+            }
+
+            (!reasons.is_empty()).then_some(SaleAnomaly { sale, reasons })
+        })
+        .collect()
+}
+
+// Discount is list price minus the price charged; lines discounted before reasons were recorded are left out.
+#[query(guard = "caller_can_read")]
+fn discount_report(start: u64, end: u64) -> ApiResult<Vec<(DiscountReason, f64, u64)>> {
+    validate_window(start, end)?;
+    let mut by_reason: BTreeMap<DiscountReason, (f64, u64)> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            for item in sale.items {
+                if let Some(reason) = item.discount_reason {
+                    let entry = by_reason.entry(reason).or_insert((0.0, 0));
+                    entry.0 += (item.list_price - item.unit_price) * item.quantity as f64;
+                    entry.1 += 1;
+                }
+            }
+        }
+    });
+    Ok(by_reason
+        .into_iter()
+        .map(|(reason, (discount, lines))| (reason, round_currency(discount), lines))
+        .collect())
+}
+
+// Active hours are the distinct clock hours in which a cashier recorded at least one sale.
+#[query(guard = "caller_can_read")]
+fn employee_productivity(start: u64, end: u64) -> ApiResult<Vec<EmployeeProductivity>> {
+    validate_window(start, end)?;
+    let mut by_cashier: BTreeMap<Principal, (BTreeSet<i64>, f64)> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            if let Some(cashier) = sale.cashier {
+                let entry = by_cashier.entry(cashier).or_default();
+                entry.0.insert(period_index(sale.timestamp, Granularity::Hour));
+                entry.1 += sale.total_amount;
+            }
+        }
+    });
+
+    let mut productivity: Vec<EmployeeProductivity> = by_cashier
+        .into_iter()
+        .map(|(principal, (hours, revenue))| EmployeeProductivity {
+            principal,
+            hours_with_sales: hours.len() as u64,
+            total_revenue: round_currency(revenue),
+            revenue_per_active_hour: round_currency(revenue / hours.len() as f64),
+        })
+        .collect();
+    productivity.sort_by(|a, b| b.revenue_per_active_hour.total_cmp(&a.revenue_per_active_hour));
+    Ok(productivity)
+}
+
+#[query(guard = "caller_can_read")]
+fn revenue_by_channel(start: u64, end: u64) -> ApiResult<Vec<(Channel, f64)>> {
+    validate_window(start, end)?;
+    let mut by_channel: BTreeMap<Channel, f64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp <= end) {
+            *by_channel.entry(sale.channel).or_insert(0.0) += sale.total_amount;
+        }
+    });
+    Ok(by_channel
+        .into_iter()
+        .map(|(channel, revenue)| (channel, round_currency(revenue)))
+        .collect())
+}
+
+// Slots are local clock hours (configured UTC offset) of the calendar day containing `day_start`.
+#[query(guard = "caller_can_read")]
+fn hourly_breakdown(day_start: u64) -> [HourStat; 24] {
+    let start = local_day_start(local_day_number(day_start));
+    let end = local_day_start(local_day_number(day_start) + 1);
+    let mut hours = [HourStat::default(); 24];
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= start && sale.timestamp < end) {
+            let hour = &mut hours[((sale.timestamp - start) as i128 / NANOS_PER_HOUR) as usize];
+            hour.revenue += sale.total_amount;
+            hour.num_sales += 1;
+            hour.units += sale.items.iter().map(|item| item.quantity).sum::<u64>();
+        }
+    });
+    for hour in hours.iter_mut() {
+        hour.revenue = round_currency(hour.revenue);
+    }
+    hours
+}
+
+#[query(guard = "caller_can_read")]
+fn end_of_day_report(day_start: u64) -> EndOfDayReport {
+    let (start, end) = day_bounds(day_start);
+    let day_sales: Vec<SaleRecord> = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= start && sale.timestamp < end)
+            .collect()
+    });
+
+    let top_sellers = rank_sellers(day_sales.iter().flat_map(|sale| sale.items.iter()), END_OF_DAY_TOP_SELLERS);
+
+    let returns: Vec<ReturnRecord> = RETURNS.with(|returns| {
+        returns
+            .borrow()
+            .iter()
+            .filter(|record| record.timestamp >= start && record.timestamp < end)
+            .cloned()
+            .collect()
+    });
+    let default_reorder_point = CONFIG.with(|config| config.borrow().default_reorder_point);
+
+    EndOfDayReport {
+        day_start: start,
+        day_end: end,
+        num_sales: day_sales.len() as u64,
+        units_sold: day_sales
+            .iter()
+            .flat_map(|sale| sale.items.iter())
+            .map(|item| item.quantity)
+            .sum(),
+        revenue: round_currency(day_sales.iter().map(|sale| sale.total_amount).sum()),
+        tax_collected: round_currency(day_sales.iter().map(|sale| sale.tax_amount).sum()),
+        profit: round_currency(day_sales.iter().map(|sale| sale.profit).sum()),
+        top_sellers,
+        refunded: round_currency(returns.iter().map(|record| record.refund_amount).sum()),
+        returns,
+        low_stock: reorder_suggestions(default_reorder_point),
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn large_sales(min_total: f64, start: u64, end: u64) -> ApiResult<Vec<SaleRecord>> {
+    if min_total.is_nan() || min_total < 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Minimum total must be zero or greater.".to_string(),
+        ));
+    }
+    validate_window(start, end)?;
+
+    let mut large_sales: Vec<SaleRecord> = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= start && sale.timestamp <= end && sale.total_amount >= min_total)
+            .collect()
+    });
+    large_sales.sort_by(|a, b| b.total_amount.total_cmp(&a.total_amount));
+    Ok(large_sales)
+}
+
+#[query(guard = "caller_can_read")]
+fn below_cost_sales(start: u64, end: u64) -> ApiResult<Vec<(SaleRecord, f64)>> {
+    validate_window(start, end)?;
+
+    let mut leaks: Vec<(SaleRecord, f64)> = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= start && sale.timestamp <= end)
+            .filter_map(|sale| {
+                let loss: f64 = sale
+                    .items
+                    .iter()
+                    .filter_map(|item| {
+                        let cost = item.unit_cost.filter(|cost| item.unit_price < *cost)?;
+                        Some((cost - item.unit_price) * item.quantity as f64)
+                    })
+                    .sum();
+                (loss > 0.0).then(|| (sale, round_currency(loss)))
+            })
+            .collect()
+    });
+    leaks.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.seq.cmp(&b.0.seq)));
+    Ok(leaks)
+}
+
+#[query(guard = "caller_can_read")]
+fn sales_containing_item(id: ItemId, start: u64, end: u64) -> ApiResult<Vec<SaleRecord>> {
+    validate_window(start, end)?;
+    let mut matching: Vec<SaleRecord> = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= start && sale.timestamp <= end)
+            .filter(|sale| sale.items.iter().any(|item| item.id == id))
+            .collect()
+    });
+    matching.sort_by_key(|sale| (sale.timestamp, sale.seq));
+    Ok(matching)
+}
+
+#[query(guard = "caller_can_read")]
+fn item_sales_report(id: ItemId, start: u64, end: u64) -> ApiResult<ItemSalesReport> {
+    validate_window(start, end)?;
+    let mut report = empty_item_report(id);
+
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            if sale.timestamp < start || sale.timestamp > end {
+                continue;
+            }
+            add_sale_to_report(&mut report, &sale);
+        }
+    });
+
+    finish_item_report(&mut report);
+    Ok(report)
+}
+
+#[query(guard = "caller_can_read")]
+fn category_item_breakdown(category: String, start: u64, end: u64) -> ApiResult<Vec<ItemSalesReport>> {
+    validate_window(start, end)?;
+    let category = normalize_category(&category)?;
+    let mut reports: BTreeMap<ItemId, ItemSalesReport> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| {
+                item.category
+                    .as_ref()
+                    .is_some_and(|item_category| item_category.eq_ignore_ascii_case(&category))
+            })
+            .map(|item| (item.id, empty_item_report(item.id)))
+            .collect()
+    });
+
+    SALES.with(|sales| {
+        let sales = sales.borrow();
+        let in_window = |sale: &SaleRecord| sale.timestamp >= start && sale.timestamp <= end;
+        for sale in sales.iter().filter(in_window) {
+            for item in &sale.items {
+                if item
+                    .category
+                    .as_ref()
+                    .is_some_and(|item_category| item_category.eq_ignore_ascii_case(&category))
+                {
+                    reports.entry(item.id).or_insert_with(|| empty_item_report(item.id));
+                }
+            }
+        }
+        for sale in sales.iter().filter(in_window) {
+            for report in reports.values_mut() {
+                add_sale_to_report(report, &sale);
+            }
+        }
+    });
+
+    let mut breakdown: Vec<ItemSalesReport> = reports.into_values().collect();
+    for report in &mut breakdown {
+        finish_item_report(report);
+    }
+    breakdown.sort_by(|a, b| b.revenue.total_cmp(&a.revenue).then(a.id.cmp(&b.id)));
+    Ok(breakdown)
+}
+
+fn empty_item_report(id: ItemId) -> ItemSalesReport {
+    ItemSalesReport {
+        id,
+        units_sold: 0,
+        revenue: 0.0,
+        num_sales: 0,
+        avg_units_per_sale: 0.0,
+        first_sold: None,
+        last_sold: None,
+    }
+}
+
+fn add_sale_to_report(report: &mut ItemSalesReport, sale: &SaleRecord) {
+    let mut in_sale = false;
+    for item in sale.items.iter().filter(|item| item.id == report.id) {
+        report.units_sold += item.quantity;
+        report.revenue += item.unit_price * item.quantity as f64;
+        in_sale = true;
+    }
+    if in_sale {
+        report.num_sales += 1;
+        report.first_sold = Some(report.first_sold.map_or(sale.timestamp, |first| first.min(sale.timestamp)));
+        report.last_sold = Some(report.last_sold.map_or(sale.timestamp, |last| last.max(sale.timestamp)));
+    }
+}
+
+fn finish_item_report(report: &mut ItemSalesReport) {
+    if report.num_sales > 0 {
+        report.avg_units_per_sale = report.units_sold as f64 / report.num_sales as f64;
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn financial_overview() -> (f64, f64) {
+    let total_sales: f64 = SALES.with(|sales| sales.borrow().iter().map(|sale| sale.total_amount).sum::<f64>())
+        + ARCHIVED_MONTHS.with(|months| months.borrow().values().map(|month| month.revenue).sum::<f64>());
+    let inventory_value: f64 = INVENTORY.with(|inventory| {
+        inventory.borrow().values().map(|item| item.quantity as f64 * item.price).sum()
+    });
+    (round_currency(total_sales), round_currency(inventory_value))
+}
+
+#[query(guard = "caller_can_read")]
+fn profitability() -> Profitability {
+    let mut revenue = 0.0;
+    let mut costed_revenue = 0.0;
+    let mut cost_of_goods = 0.0;
+
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            for item in &sale.items {
+                let line_revenue = item.unit_price * item.quantity as f64;
+                revenue += line_revenue;
+                if let Some(unit_cost) = item.unit_cost {
+                    costed_revenue += line_revenue;
+                    cost_of_goods += unit_cost * item.quantity as f64;
+                }
+            }
+        }
+    });
+
+    let gross_profit = costed_revenue - cost_of_goods;
+    let gross_margin_percent = if costed_revenue > 0.0 {
+        (gross_profit / costed_revenue * 10_000.0).round() / 100.0
+    } else {
+        0.0
+    };
+
+    Profitability {
+        revenue,
+        cost_of_goods,
+        gross_profit,
+        gross_margin_percent,
+        uncosted_revenue: revenue - costed_revenue,
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn low_margin_items(min_margin_percent: f64) -> Vec<(InventoryItem, f64)> {
+    let mut low_margin: Vec<(InventoryItem, f64)> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter_map(|item| {
+                let margin = margin_percent(item.price, item.cost_price?);
+                (margin < min_margin_percent).then(|| (item.clone(), margin))
+            })
+            .collect()
+    });
+    low_margin.sort_by(|a, b| a.1.total_cmp(&b.1));
+    low_margin
+}
+
+#[query(guard = "caller_can_read")]
+fn inventory_aging() -> Vec<AgingBucket> {
+    let mut buckets: Vec<AgingBucket> = Vec::new();
+    let mut min_days = 0;
+    for max_days in AGING_BUCKET_BOUNDS_DAYS.iter().copied().map(Some).chain([None]) {
+        buckets.push(AgingBucket {
+            min_days,
+            max_days,
+            item_count: 0,
+            units: 0,
+            value: 0.0,
+        });
+        min_days = max_days.map_or(0, |max| max + 1);
+    }
+
+    let last_sold = last_sold_times();
+    let now = time();
+    INVENTORY.with(|inventory| {
+        for item in inventory.borrow().values().filter(|item| item.quantity > 0) {
+            let since = last_sold.get(&item.id).copied().unwrap_or(item.created_at);
+            let age_days = now.saturating_sub(since) / NANOS_PER_DAY as u64;
+            let bucket = buckets
+                .iter_mut()
+                .find(|bucket| bucket.max_days.is_none_or(|max| age_days <= max))
+                .expect("the last aging bucket is unbounded");
+            bucket.item_count += 1;
+            bucket.units = bucket.units.saturating_add(item.quantity);
+            bucket.value += item.quantity as f64 * item.price;
+        }
+    });
+    buckets
+}
+
+#[query(guard = "caller_can_read")]
+fn price_distribution(bucket_size: f64) -> ApiResult<Vec<(f64, u64)>> {
+    if !bucket_size.is_finite() || bucket_size <= 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Bucket size must be a positive amount.".to_string(),
+        ));
+    }
+
+    let mut buckets: BTreeMap<u64, u64> = BTreeMap::new();
+    INVENTORY.with(|inventory| {
+        for item in inventory.borrow().values() {
+            *buckets.entry((item.price / bucket_size).floor() as u64).or_insert(0) += 1;
+        }
+    });
+    Ok(buckets
+        .into_iter()
+        .map(|(index, item_count)| (index as f64 * bucket_size, item_count))
+        .collect())
+}
+
+#[query(guard = "caller_can_read")]
+fn inventory_health() -> HealthScore {
+    let now = time();
+    let last_sold = last_sold_times();
+    let cover_since = now.saturating_sub(COVER_WINDOW_DAYS * NANOS_PER_DAY as u64);
+    let mut recent_units: BTreeMap<ItemId, u64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= cover_since) {
+            for item in &sale.items {
+                *recent_units.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+    let default_reorder_point = CONFIG.with(|config| config.borrow().default_reorder_point);
+
+    let (mut total, mut in_stock, mut below_reorder, mut dead) = (0u64, 0u64, 0u64, 0u64);
+    let mut covers: Vec<f64> = Vec::new();
+    INVENTORY.with(|inventory| {
+        for item in inventory.borrow().values() {
+            total += 1;
+            if item.quantity > 0 {
+                in_stock += 1;
+                let since = last_sold.get(&item.id).copied().unwrap_or(item.created_at);
+                if now.saturating_sub(since) / NANOS_PER_DAY as u64 > DEAD_STOCK_DAYS {
+                    dead += 1;
+                }
+            }
+            if item.quantity < effective_reorder_point(item, default_reorder_point) {
+                below_reorder += 1;
+            }
+            if let Some(units) = recent_units.get(&item.id).filter(|units| **units > 0) {
+                covers.push(item.quantity as f64 / (*units as f64 / COVER_WINDOW_DAYS as f64));
+            }
+        }
+    });
+
+    let percent = |count: u64| if total > 0 { count as f64 / total as f64 * 100.0 } else { 0.0 };
+    let in_stock_percent = percent(in_stock);
+    let below_reorder_percent = percent(below_reorder);
+    let dead_stock_percent = percent(dead);
+    let avg_days_of_cover = (!covers.is_empty()).then(|| covers.iter().sum::<f64>() / covers.len() as f64);
+
+    let score = if total == 0 {
+        0.0
+    } else {
+        let mut weighted = HEALTH_IN_STOCK_WEIGHT * in_stock_percent
+            + HEALTH_REORDER_WEIGHT * (100.0 - below_reorder_percent)
+            + HEALTH_DEAD_STOCK_WEIGHT * (100.0 - dead_stock_percent);
+        let mut weights = HEALTH_IN_STOCK_WEIGHT + HEALTH_REORDER_WEIGHT + HEALTH_DEAD_STOCK_WEIGHT;
+        if let Some(cover) = avg_days_of_cover {
+            weighted += HEALTH_COVER_WEIGHT * (cover / TARGET_DAYS_OF_COVER).min(1.0) * 100.0;
+            weights += HEALTH_COVER_WEIGHT;
+        }
+        (weighted / weights).clamp(0.0, 100.0)
+    };
+
+    HealthScore {
+        score,
+        in_stock_percent,
+        below_reorder_percent,
+        dead_stock_percent,
+        avg_days_of_cover,
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn items_at_safety_stock() -> Vec<InventoryItem> {
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.safety_stock.is_some_and(|safety_stock| item.quantity <= safety_stock))
+            .cloned()
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn reorder_schedule(horizon_ns: u64) -> Vec<ScheduledReorder> {
+    let now = time();
+    let horizon_end = now.saturating_add(horizon_ns);
+    let cover_since = now.saturating_sub(COVER_WINDOW_DAYS * NANOS_PER_DAY as u64);
+    let mut recent_units: BTreeMap<ItemId, u64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= cover_since) {
+            for item in &sale.items {
+                *recent_units.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+
+    let mut schedule: Vec<ScheduledReorder> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.active)
+            .filter_map(|item| {
+                let units = recent_units.get(&item.id).copied().filter(|units| *units > 0)?;
+                let daily_velocity = units as f64 / COVER_WINDOW_DAYS as f64;
+                let days_left = available_quantity(item) as f64 / daily_velocity;
+                let stockout_at = now.saturating_add((days_left * NANOS_PER_DAY as f64) as u64);
+                let lead_time_ns = item.lead_time_days.unwrap_or(0).saturating_mul(NANOS_PER_DAY as u64);
+                let order_by = stockout_at.saturating_sub(lead_time_ns).max(now);
+                if order_by > horizon_end {
+                    return None;
+                }
+                let suggested_quantity = item
+                    .target_stock
+                    .unwrap_or_else(|| (daily_velocity * TARGET_DAYS_OF_COVER).ceil() as u64)
+                    .max(1);
+                Some(ScheduledReorder {
+                    item: item.clone(),
+                    order_by,
+                    stockout_at,
+                    suggested_quantity,
+                })
+            })
+            .collect()
+    });
+    schedule.sort_by(|a, b| a.order_by.cmp(&b.order_by).then(a.item.id.cmp(&b.item.id)));
+    schedule
+}
+
+// Markdowns grow with how many times over `overstock_days` the cover is, never pricing below cost plus the minimum margin.
+#[query(guard = "caller_can_read")]
+fn suggest_markdowns(overstock_days: u64) -> ApiResult<Vec<MarkdownSuggestion>> {
+    if overstock_days == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Overstock days must be greater than zero.".to_string(),
+        ));
+    }
+    let now = time();
+    let cover_since = now.saturating_sub(COVER_WINDOW_DAYS * NANOS_PER_DAY as u64);
+    let mut recent_units: BTreeMap<ItemId, u64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= cover_since) {
+            for item in &sale.items {
+                *recent_units.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+    let (min_margin, enforced_margin) = CONFIG.with(|config| {
+        let config = config.borrow();
+        (config.markdown_min_margin_percent, config.min_margin_percent)
+    });
+
+    let mut suggestions: Vec<MarkdownSuggestion> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.quantity > 0 && item.active)
+            .filter_map(|item| {
+                let daily_velocity = recent_units.get(&item.id).copied().unwrap_or(0) as f64 / COVER_WINDOW_DAYS as f64;
+                let days_of_cover = (daily_velocity > 0.0).then(|| item.quantity as f64 / daily_velocity);
+                let markdown_percent = match days_of_cover {
+                    Some(cover) if cover <= overstock_days as f64 => return None,
+                    Some(cover) => (cover / overstock_days as f64 - 1.0) * MARKDOWN_PERCENT_PER_OVERSTOCK_MULTIPLE,
+                    None => OVERSTOCK_MAX_MARKDOWN_PERCENT,
+                };
+                let floor_price = item.cost_price.map_or(0.0, |cost| {
+                    let enforced_floor = enforced_margin.map_or(0.0, |margin| cost / (1.0 - margin / 100.0));
+                    (cost * (1.0 + min_margin / 100.0)).max(enforced_floor)
+                });
+                let floor_percent = (1.0 - floor_price / item.price) * 100.0;
+                let markdown_percent = markdown_percent.min(OVERSTOCK_MAX_MARKDOWN_PERCENT).min(floor_percent).floor();
+                if markdown_percent <= 0.0 {
+                    return None;
+                }
+
+                let lifted_velocity = daily_velocity * (1.0 + MARKDOWN_DEMAND_ELASTICITY * markdown_percent / 100.0);
+                Some(MarkdownSuggestion {
+                    item_id: item.id,
+                    name: item.name.clone(),
+                    current_price: item.price,
+                    suggested_price: round_currency(item.price * (1.0 - markdown_percent / 100.0)).max(floor_price),
+                    markdown_percent,
+                    days_of_cover,
+                    projected_days_of_cover: days_of_cover.map(|_| item.quantity as f64 / lifted_velocity),
+                })
+            })
+            .collect()
+    });
+    suggestions.sort_by(|a, b| {
+        b.markdown_percent
+            .total_cmp(&a.markdown_percent)
+            .then(a.item_id.cmp(&b.item_id))
+    });
+    Ok(suggestions)
+}
+
+#[query(guard = "caller_can_read")]
+fn clearance_candidates() -> Vec<ClearanceSuggestion> {
+    let now = time();
+    let last_sold = last_sold_times();
+    let mut units_sold: BTreeMap<ItemId, u64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            for item in &sale.items {
+                *units_sold.entry(item.id).or_insert(0) += item.quantity;
+            }
+        }
+    });
+
+    let mut candidates: Vec<ClearanceSuggestion> = INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter(|item| item.quantity > 0)
+            .filter_map(|item| {
+                let since = last_sold.get(&item.id).copied().unwrap_or(item.created_at);
+                let days_since_last_sale = now.saturating_sub(since) / NANOS_PER_DAY as u64;
+                if days_since_last_sale < CLEARANCE_MIN_IDLE_DAYS {
+                    return None;
+                }
+
+                let days_listed = (now.saturating_sub(item.created_at) / NANOS_PER_DAY as u64).max(1);
+                let daily_velocity = units_sold.get(&item.id).copied().unwrap_or(0) as f64 / days_listed as f64;
+                let days_of_cover = (daily_velocity > 0.0).then(|| item.quantity as f64 / daily_velocity);
+                if days_of_cover.is_some_and(|cover| cover <= CLEARANCE_OVERSTOCK_DAYS_OF_COVER) {
+                    return None;
+                }
+
+                let cost_floor_percent = item
+                    .cost_price
+                    .map_or(CLEARANCE_MAX_MARKDOWN_PERCENT, |cost| (1.0 - cost / item.price) * 100.0);
+                let markdown_percent = (days_since_last_sale as f64 / 30.0 * CLEARANCE_MARKDOWN_PER_MONTH_IDLE)
+                    .min(CLEARANCE_MAX_MARKDOWN_PERCENT)
+                    .min(cost_floor_percent)
+                    .floor();
+                if markdown_percent <= 0.0 {
+                    return None;
+                }
+
+                Some(ClearanceSuggestion {
+                    item: item.clone(),
+                    days_since_last_sale,
+                    days_of_cover,
+                    markdown_percent,
+                    suggested_price: round_currency(item.price * (1.0 - markdown_percent / 100.0)),
+                })
+            })
+            .collect()
+    });
+    candidates.sort_by(|a, b| {
+        b.days_since_last_sale
+            .cmp(&a.days_since_last_sale)
+            .then(a.item.id.cmp(&b.item.id))
+    });
+    candidates
+}
+
+fn last_sold_times() -> BTreeMap<ItemId, u64> {
+    let mut last_sold = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            for item in &sale.items {
+                let last = last_sold.entry(item.id).or_insert(sale.timestamp);
+                *last = (*last).max(sale.timestamp);
+            }
+        }
+    });
+    last_sold
+}
+
+#[query(guard = "caller_can_read")]
+fn unit_economics(id: ItemId) -> Option<UnitEconomics> {
+    let item = INVENTORY.with(|inventory| inventory.borrow().get(&id).cloned())?;
+    let cost_price = item.cost_price?;
+    let unit_margin = item.price - cost_price;
+    let markup_percent = if cost_price > 0.0 {
+        unit_margin / cost_price * 100.0
+    } else {
+        0.0
+    };
+
+    Some(UnitEconomics {
+        price: item.price,
+        cost_price,
+        unit_margin,
+        margin_percent: margin_percent(item.price, cost_price),
+        markup_percent,
+    })
+}
+
+fn margin_percent(price: f64, cost_price: f64) -> f64 {
+    if price > 0.0 {
+        (price - cost_price) / price * 100.0
+    } else {
+        0.0
+    }
+}
+
+// Projections are per `COVER_WINDOW_DAYS` and assume the last window's unit sales carry over at the new price.
+#[query(guard = "caller_can_read")]
+fn simulate_price_change(id: ItemId, new_price: f64) -> ApiResult<PriceSimulation> {
+    if new_price <= 0.0 {
+        return Err(BusinessError::InvalidInput(
+            "Simulated price must be positive.".to_string(),
+        ));
+    }
+    check_price_magnitude(new_price)?;
+    let item = INVENTORY
+        .with(|inventory| inventory.borrow().get(&id).cloned())
+        .ok_or_else(|| BusinessError::NotFound(format!("Item with ID {} not found.", id)))?;
+    let cost_price = item
+        .cost_price
+        .ok_or_else(|| BusinessError::InvalidInput(format!("Item with ID {} has no cost price.", id)))?;
+
+    let since = time().saturating_sub(COVER_WINDOW_DAYS * NANOS_PER_DAY as u64);
+    let recent_units: u64 = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= since)
+            .flat_map(|sale| sale.items)
+            .filter(|line| line.id == id)
+            .map(|line| line.quantity)
+            .sum()
+    });
+    let price_delta = new_price - item.price;
+    let projection = (recent_units > 0).then(|| round_currency(price_delta * recent_units as f64));
+
+    Ok(PriceSimulation {
+        item_id: id,
+        cost_price,
+        current_price: item.price,
+        simulated_price: new_price,
+        current_unit_margin: item.price - cost_price,
+        simulated_unit_margin: new_price - cost_price,
+        current_margin_percent: margin_percent(item.price, cost_price),
+        simulated_margin_percent: margin_percent(new_price, cost_price),
+        daily_units: recent_units as f64 / COVER_WINDOW_DAYS as f64,
+        projected_revenue_change: projection,
+        projected_profit_change: projection,
+    })
+}
+
+// Changes within `TREND_FLAT_PERCENT` either way count as flat; no prior sales leaves the percentage undefined.
+#[query(guard = "caller_can_read")]
+fn item_trend(id: ItemId, window_ns: u64) -> ApiResult<Trend> {
+    if window_ns == 0 {
+        return Err(BusinessError::InvalidInput(
+            "Trend window must be greater than zero.".to_string(),
+        ));
+    }
+    let now = time();
+    let current_start = now.saturating_sub(window_ns);
+    let prior_start = current_start.saturating_sub(window_ns);
+    let (mut current_units, mut prior_units) = (0u64, 0u64);
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.timestamp >= prior_start) {
+            let units: u64 = sale.items.iter().filter(|item| item.id == id).map(|item| item.quantity).sum();
+            if sale.timestamp >= current_start {
+                current_units += units;
+            } else {
+                prior_units += units;
+            }
+        }
+    });
+
+    let percent_change = (prior_units > 0)
+        .then(|| (current_units as f64 - prior_units as f64) / prior_units as f64 * 100.0);
+    let direction = match percent_change {
+        None if current_units == 0 => TrendDirection::NoData,
+        None => TrendDirection::Rising,
+        Some(change) if change > TREND_FLAT_PERCENT => TrendDirection::Rising,
+        Some(change) if change < -TREND_FLAT_PERCENT => TrendDirection::Falling,
+        Some(_) => TrendDirection::Flat,
+    };
+    Ok(Trend {
+        direction,
+        current_units,
+        prior_units,
+        percent_change,
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn target_progress() -> TargetProgress {
+    let target = CONFIG.with(|config| config.borrow().monthly_target);
+    let now = time();
+    let (year, month, _) = civil_from_days(local_day_number(now));
+    let first_day = days_from_civil(year, month, 1);
+    let month_start = local_day_start(first_day);
+    let month_end = local_day_start(first_day + days_in_month(year, month) as i64);
+
+    let achieved: f64 = SALES.with(|sales| {
+        sales
+            .borrow()
+            .iter()
+            .filter(|sale| sale.timestamp >= month_start && sale.timestamp < month_end)
+            .map(|sale| sale.profit)
+            .sum()
+    });
+
+    let elapsed = now.saturating_sub(month_start) as f64 / month_end.saturating_sub(month_start).max(1) as f64;
+    let projected_month_end = if elapsed > 0.0 { achieved / elapsed.min(1.0) } else { achieved };
+    let percent = if target > 0.0 { achieved / target * 100.0 } else { 0.0 };
+
+    TargetProgress {
+        target,
+        achieved: round_currency(achieved),
+        percent,
+        projected_month_end: round_currency(projected_month_end),
+    }
+}
+
+#[query(guard = "caller_can_read")]
+fn reorder_suggestions(threshold: u64) -> Vec<ReorderSuggestion> {
+    INVENTORY.with(|inventory| {
+        inventory
+            .borrow()
+            .values()
+            .filter_map(|item| {
+                let reorder_point = effective_reorder_point(item, threshold);
+                if item.quantity >= reorder_point {
+                    return None;
+                }
+                Some(ReorderSuggestion {
+                    item: item.clone(),
+                    to_order: quantity_to_order(item, reorder_point),
+                })
+            })
+            .collect()
+    })
+}
+
+#[query(guard = "caller_can_read")]
+fn restock_cost_estimate() -> RestockCostEstimate {
+    let default_reorder_point = CONFIG.with(|config| config.borrow().default_reorder_point);
+    let mut estimate = RestockCostEstimate {
+        total_cost: 0.0,
+        uncosted_items: Vec::new(),
+    };
+
+    INVENTORY.with(|inventory| {
+        for item in inventory.borrow().values() {
+            let reorder_point = effective_reorder_point(item, default_reorder_point);
+            if item.quantity >= reorder_point {
+                continue;
+            }
+            match item.cost_price {
+                Some(cost_price) => estimate.total_cost += quantity_to_order(item, reorder_point) as f64 * cost_price,
+                None => estimate.uncosted_items.push(item.id),
+            }
+        }
+    });
+    estimate
+}
+
+fn quantity_to_order(item: &InventoryItem, reorder_point: u64) -> u64 {
+    item.target_stock
+        .unwrap_or_else(|| reorder_point.saturating_mul(2))
+        .saturating_sub(item.quantity)
+}
+
+#[query(guard = "caller_can_read")]
+fn top_categories(n: usize, start: u64, end: u64) -> ApiResult<Vec<(String, f64, u64)>> {
+    validate_window(start, end)?;
+    let mut totals: BTreeMap<String, (f64, u64)> = BTreeMap::new();
+
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter() {
+            if sale.timestamp < start || sale.timestamp > end {
+                continue;
+            }
+            for item in &sale.items {
+                let category = item.category.clone().unwrap_or_else(|| UNCATEGORIZED.to_string());
+                let entry = totals.entry(category).or_insert((0.0, 0));
+                entry.0 += item.unit_price * item.quantity as f64;
+                entry.1 += item.quantity;
+            }
+        }
+    });
+
+    let mut ranking: Vec<(String, f64, u64)> = totals
+        .into_iter()
+        .map(|(category, (revenue, units))| (category, revenue, units))
+        .collect();
+    ranking.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranking.truncate(n.min(MAX_TOP_N));
+    Ok(ranking)
+}
+
+#[query(guard = "caller_can_read")]
+fn frequently_bought_together(item_id: ItemId, top: usize) -> Vec<(ItemId, u64)> {
+    let mut counts: BTreeMap<ItemId, u64> = BTreeMap::new();
+    SALES.with(|sales| {
+        for sale in sales.borrow().iter().filter(|sale| sale.items.iter().any(|item| item.id == item_id)) {
+            let others: BTreeSet<ItemId> = sale.items.iter().map(|item| item.id).filter(|id| *id != item_id).collect();
+            for id in others {
+                *counts.entry(id).or_insert(0) += 1;
+            }
+        }
+    });
+    let mut ranking: Vec<(ItemId, u64)> = counts.into_iter().collect();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranking.truncate(top.min(MAX_TOP_N));
+    ranking
+}
+
+#[query(guard = "caller_can_read")]
+fn get_top_selling_items(n: usize) -> Vec<(String, u64)> {
+    SALES.with(|sales| rank_sellers(sales.borrow().iter().flat_map(|sale| sale.items), n.min(MAX_TOP_N)))
+}
+
+fn rank_sellers<I: Borrow<SaleItem>>(items: impl Iterator<Item = I>, n: usize) -> Vec<(String, u64)> {
+    let mut sales_count: HashMap<ItemId, (String, u64)> = HashMap::new();
+    for item in items {
+        let item = item.borrow();
+        let entry = sales_count.entry(item.id).or_insert_with(|| (item.name.clone(), 0));
+        entry.0.clone_from(&item.name);
+        entry.1 += item.quantity;
+    }
+
+    let mut sales_vec: Vec<_> = sales_count.into_values().collect();
+    sales_vec.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sales_vec.truncate(n);
+    sales_vec
+}
+
+// One row per exported method, in definition order; add a row alongside every new query or update.
+// `Read` methods honour allow_public_read, `Authenticated` ones reject anonymous callers,
+// and `Controller` ones also accept the owner set at install.
+#[rustfmt::skip]
+const API_METHODS: &[(&str, MethodKind, MethodAccess, &str, &[&str])] = &[
+    ("get_stock_alerts", MethodKind::Query, MethodAccess::Read, "Low-stock and back-in-stock alerts raised since a timestamp.", &["since"]),
+    ("get_config", MethodKind::Query, MethodAccess::Read, "Returns the current canister settings.", &[]),
+    ("set_max_sale_lines", MethodKind::Update, MethodAccess::Controller, "Caps the number of lines in a single sale (default 500).", &["max_sale_lines"]),
+    ("set_utc_offset", MethodKind::Update, MethodAccess::Authenticated, "Sets the shop's timezone used for calendar-day reports.", &["utc_offset_minutes"]),
+    ("set_default_reorder_point", MethodKind::Update, MethodAccess::Authenticated, "Reorder point for items without their own or a category threshold (default 0).", &["default_reorder_point"]),
+    ("set_category_reorder_threshold", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears a category's reorder point.", &["category", "threshold"]),
+    ("set_currency", MethodKind::Update, MethodAccess::Controller, "Sets the base currency code and decimal places for rounding.", &["code", "decimal_places"]),
+    ("set_value_caps", MethodKind::Update, MethodAccess::Controller, "Sets the maximum price and per-item quantity.", &["max_price", "max_quantity_per_item"]),
+    ("set_rate_limit", MethodKind::Update, MethodAccess::Controller, "Sets the per-caller update rate limit.", &["capacity", "refill_per_minute"]),
+    ("set_valuation_method", MethodKind::Update, MethodAccess::Authenticated, "Chooses how restock costs update an item's cost price: last cost or weighted average.", &["valuation_method"]),
+    ("set_barcode_format", MethodKind::Update, MethodAccess::Authenticated, "Requires SKUs to be valid EAN-13 or UPC-A barcodes, or disables the check.", &["barcode_format"]),
+    ("set_tax_rate", MethodKind::Update, MethodAccess::Controller, "Sets the sales tax rate applied to non-exempt lines (default 0).", &["percent"]),
+    ("set_markdown_min_margin", MethodKind::Update, MethodAccess::Controller, "Sets the minimum margin over cost kept by suggested markdowns.", &["percent"]),
+    ("set_min_margin", MethodKind::Update, MethodAccess::Controller, "Sets or clears the minimum margin enforced on lowered prices, and whether breaches are rejected or capped.", &["min_margin_percent", "margin_policy"]),
+    ("set_exchange_rate", MethodKind::Update, MethodAccess::Controller, "Sets how many base-currency units one unit of a foreign currency is worth.", &["code", "rate_to_base"]),
+    ("set_allow_public_read", MethodKind::Update, MethodAccess::Controller, "Toggles whether anonymous callers may use queries.", &["allow"]),
+    ("set_allow_zero_initial_stock", MethodKind::Update, MethodAccess::Authenticated, "Toggles whether add_item accepts items with no stock yet.", &["allow"]),
+    ("set_min_sale_amount", MethodKind::Update, MethodAccess::Controller, "Sets the minimum sale total (0 disables it).", &["amount"]),
+    ("set_monthly_target", MethodKind::Update, MethodAccess::Authenticated, "Gross-profit goal for each calendar month.", &["amount"]),
+    ("set_reservation_ttl", MethodKind::Update, MethodAccess::Authenticated, "Sets how long a reservation holds stock (default 30 minutes).", &["reservation_ttl_ns"]),
+    ("set_pending_sale_window", MethodKind::Update, MethodAccess::Authenticated, "Sets how long a pending sale may stay open before it is auto-cancelled (default 15 minutes).", &["pending_sale_window_ns"]),
+    ("set_sale_amend_window", MethodKind::Update, MethodAccess::Authenticated, "Sets how long after a sale it may still be amended.", &["sale_amend_window_ns"]),
+    ("set_sales_retention", MethodKind::Update, MethodAccess::Controller, "Sets or clears how long sales stay in the active history before archiving.", &["sales_retention_ns"]),
+    ("set_snapshot_interval", MethodKind::Update, MethodAccess::Controller, "Sets or clears how often maintenance snapshots the canister state.", &["snapshot_interval_ns"]),
+    ("take_snapshot", MethodKind::Update, MethodAccess::Controller, "Writes a snapshot of the whole canister state now.", &[]),
+    ("list_snapshots", MethodKind::Query, MethodAccess::Read, "Retained snapshots, oldest first.", &[]),
+    ("rollback_to_snapshot", MethodKind::Update, MethodAccess::Controller, "Restores the whole canister state from a retained snapshot.", &["id"]),
+    ("archive_old_sales", MethodKind::Update, MethodAccess::Controller, "Rolls sales older than the retention window into monthly totals.", &[]),
+    ("get_archived_summary", MethodKind::Query, MethodAccess::Read, "Monthly totals of archived sales.", &[]),
+    ("set_maintenance_interval", MethodKind::Update, MethodAccess::Controller, "Sets how often periodic maintenance runs (default hourly).", &["secs"]),
+    ("disable_maintenance", MethodKind::Update, MethodAccess::Controller, "Turns off periodic maintenance.", &[]),
+    ("set_day_cutoff_hour", MethodKind::Update, MethodAccess::Authenticated, "Sets the local hour at which the business day rolls over.", &["day_cutoff_hour"]),
+    ("add_item", MethodKind::Update, MethodAccess::Authenticated, "Adds an item; name and variant must be unique, and retrying with the same client key returns the existing ID.", &["name", "quantity", "price", "sku", "description", "client_key", "variant"]),
+    ("update_item", MethodKind::Update, MethodAccess::Authenticated, "Updates an item's name, quantity, price, SKU or description.", &["id", "name", "quantity", "price", "sku", "description"]),
+    ("patch_item", MethodKind::Update, MethodAccess::Authenticated, "Applies any subset of item fields atomically, validating all before saving.", &["id", "patch"]),
+    ("update_quantity_cas", MethodKind::Update, MethodAccess::Authenticated, "Sets stock only if it still equals `expected`.", &["id", "expected", "new"]),
+    ("restock_item", MethodKind::Update, MethodAccess::Authenticated, "Adds received units and returns the new quantity.", &["id", "amount", "unit_cost"]),
+    ("bulk_restock", MethodKind::Update, MethodAccess::Authenticated, "Restocks many items at once, with a result per distinct item.", &["lines"]),
+    ("set_max_stock", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears an item's shelf capacity.", &["id", "max_stock"]),
+    ("apply_price_list", MethodKind::Update, MethodAccess::Authenticated, "Bulk price update with per-line results, optionally as a dry run.", &["changes", "dry_run"]),
+    ("set_reorder_levels", MethodKind::Update, MethodAccess::Authenticated, "Sets an item's own reorder point and restock target.", &["id", "reorder_point", "target_stock"]),
+    ("set_item_category", MethodKind::Update, MethodAccess::Authenticated, "Assigns or clears an item's category.", &["id", "category"]),
+    ("set_item_tags", MethodKind::Update, MethodAccess::Authenticated, "Replaces an item's tags.", &["id", "tags"]),
+    ("tag_items", MethodKind::Update, MethodAccess::Authenticated, "Adds a tag to every item matching a filter.", &["filter", "tag"]),
+    ("set_item_cost", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears an item's cost price.", &["id", "cost_price"]),
+    ("set_item_active", MethodKind::Update, MethodAccess::Authenticated, "Discontinues or reinstates an item.", &["id", "active"]),
+    ("set_featured", MethodKind::Update, MethodAccess::Controller, "Replaces the curated featured items list.", &["ids"]),
+    ("set_online_buffer", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears the units held back from online availability for walk-ins.", &["id", "online_buffer"]),
+    ("set_safety_stock", MethodKind::Update, MethodAccess::Authenticated, "Sets a stock floor that sales may not breach without an override.", &["id", "safety_stock"]),
+    ("set_item_lead_time", MethodKind::Update, MethodAccess::Authenticated, "Days a reorder takes to arrive, used by the reorder schedule.", &["id", "lead_time_days"]),
+    ("set_item_variant", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears an item's variant, keeping name and variant unique.", &["id", "variant"]),
+    ("add_serial", MethodKind::Update, MethodAccess::Authenticated, "Registers a serial number for an item; on serialized items each serial adds a unit.", &["item_id", "serial"]),
+    ("set_item_serialized", MethodKind::Update, MethodAccess::Authenticated, "Turns serial-number tracking on or off for an item.", &["id", "serialized"]),
+    ("get_serials", MethodKind::Query, MethodAccess::Read, "Lists an item's serial numbers and their status.", &["item_id"]),
+    ("set_item_tax_exempt", MethodKind::Update, MethodAccess::Authenticated, "Marks an item as exempt from sales tax.", &["id", "tax_exempt"]),
+    ("set_item_deposit", MethodKind::Update, MethodAccess::Authenticated, "Sets or clears the returnable container deposit charged per unit.", &["id", "deposit"]),
+    ("recategorize", MethodKind::Update, MethodAccess::Authenticated, "Moves every item in a category to another category.", &["from", "to"]),
+    ("remove_item", MethodKind::Update, MethodAccess::Authenticated, "Deletes an item from the catalog.", &["id"]),
+    ("merge_items", MethodKind::Update, MethodAccess::Authenticated, "Folds a duplicate item's stock and history into another item.", &["keep", "merge"]),
+    ("recover_item", MethodKind::Update, MethodAccess::Authenticated, "Recreates a deleted item from its latest sale at zero stock and relinks its history.", &["old_id"]),
+    ("record_sale", MethodKind::Update, MethodAccess::Authenticated, "Records a sale; options set a foreign currency, a note, the channel, pending or best effort.", &["items", "options"]),
+    ("record_sale_lines", MethodKind::Update, MethodAccess::Authenticated, "Records a sale whose lines may override price, tax exemption or safety stock, or name serials.", &["lines", "options"]),
+    ("quick_sell", MethodKind::Update, MethodAccess::Authenticated, "Sells a single unit of the item with the scanned SKU.", &["sku"]),
+    ("amend_sale", MethodKind::Update, MethodAccess::Authenticated, "Replaces a recent sale's lines within the amendment window.", &["sale_seq", "new_lines"]),
+    ("split_sale", MethodKind::Update, MethodAccess::Authenticated, "Moves the given quantities of a sale into a new sale, returning both sequence numbers.", &["sale_seq", "lines_to_split"]),
+    ("confirm_sale", MethodKind::Update, MethodAccess::Authenticated, "Finalizes a pending sale and deducts its stock.", &["sale_seq"]),
+    ("cancel_sale", MethodKind::Update, MethodAccess::Authenticated, "Cancels a pending sale and releases its stock.", &["sale_seq"]),
+    ("cancel_stale_pending_sales", MethodKind::Update, MethodAccess::Authenticated, "Cancels pending sales older than the pending sale window.", &[]),
+    ("pending_sales", MethodKind::Query, MethodAccess::Read, "Lists open pending sales.", &[]),
+    ("check_availability", MethodKind::Query, MethodAccess::Read, "Shows, per distinct item, whether enough unreserved stock exists.", &["lines"]),
+    ("online_availability", MethodKind::Query, MethodAccess::Read, "Sellable units offered online after the walk-in buffer.", &["id"]),
+    ("get_item_availability", MethodKind::Query, MethodAccess::Read, "Returns an item with its reserved and sellable stock.", &["id"]),
+    ("get_inventory_availability", MethodKind::Query, MethodAccess::Read, "Returns every item with its reserved and sellable stock.", &[]),
+    ("reserve_item", MethodKind::Update, MethodAccess::Authenticated, "Holds stock so it cannot be sold to anyone else.", &["id", "quantity"]),
+    ("reserve_cart", MethodKind::Update, MethodAccess::Authenticated, "Reserves every line of a cart under one reservation, or nothing if any line is short.", &["lines"]),
+    ("release_reservation", MethodKind::Update, MethodAccess::Authenticated, "Returns reserved stock to availability.", &["reservation_id"]),
+    ("fulfill_reservation", MethodKind::Update, MethodAccess::Authenticated, "Turns a reservation into a sale.", &["reservation_id"]),
+    ("expire_stale_reservations", MethodKind::Update, MethodAccess::Authenticated, "Releases reservations older than the configured TTL.", &[]),
+    ("get_reservations", MethodKind::Query, MethodAccess::Read, "Lists open reservations.", &[]),
+    ("return_sale_items", MethodKind::Update, MethodAccess::Authenticated, "Returns units from a past sale to stock, recording why.", &["sale_seq", "items", "reason"]),
+    ("refund_deposit", MethodKind::Update, MethodAccess::Authenticated, "Refunds container deposits for units brought back from a sale.", &["sale_seq", "item_id", "quantity"]),
+    ("outstanding_deposits", MethodKind::Query, MethodAccess::Read, "Deposits collected on sales and not yet refunded.", &[]),
+    ("void_sale", MethodKind::Update, MethodAccess::Authenticated, "Voids an erroneous sale, restoring its stock and excluding it from analytics.", &["sale_seq", "reason"]),
+    ("reconcile_item", MethodKind::Query, MethodAccess::Read, "Compares an item's stock with its replayed movements.", &["id"]),
+    ("stock_ledger", MethodKind::Query, MethodAccess::Read, "Chronological stock movements for one item in a window, with the balance after each.", &["item_id", "start", "end"]),
+    ("get_returns", MethodKind::Query, MethodAccess::Read, "Retrieves return history.", &[]),
+    ("returns_by_reason", MethodKind::Query, MethodAccess::Read, "Returns and refunded value per reason within a window.", &["start", "end"]),
+    ("get_inventory", MethodKind::Query, MethodAccess::Read, "Fetches inventory details.", &[]),
+    ("get_catalog_version", MethodKind::Query, MethodAccess::Read, "Counter that changes whenever any item or its stock changes.", &[]),
+    ("get_item_details", MethodKind::Query, MethodAccess::Read, "Fetches one item by ID.", &["id"]),
+    ("get_variants", MethodKind::Query, MethodAccess::Read, "Lists every variant of a product name.", &["name"]),
+    ("inventory_count", MethodKind::Query, MethodAccess::Read, "Number of items in the catalog.", &[]),
+    ("get_featured", MethodKind::Query, MethodAccess::Read, "Featured items in curated order.", &[]),
+    ("deal_of_the_day", MethodKind::Query, MethodAccess::Read, "One overstocked or slow-moving item to feature, fixed for the whole day.", &[]),
+    ("get_item_count", MethodKind::Query, MethodAccess::Read, "Number of items in the catalog.", &[]),
+    ("get_sale_count", MethodKind::Query, MethodAccess::Read, "Number of completed sales, excluding pending, cancelled and voided ones.", &[]),
+    ("total_units", MethodKind::Query, MethodAccess::Read, "Total units in stock across all items.", &[]),
+    ("out_of_stock_count", MethodKind::Query, MethodAccess::Read, "Number of items with no stock.", &[]),
+    ("search_item_by_name", MethodKind::Query, MethodAccess::Read, "Finds items whose name contains the text.", &["name"]),
+    ("search_items", MethodKind::Query, MethodAccess::Read, "Finds items whose name or description contains the text.", &["text"]),
+    ("search_ranked", MethodKind::Query, MethodAccess::Read, "Storefront search ranked by match quality and recent popularity.", &["query", "limit"]),
+    ("search", MethodKind::Query, MethodAccess::Read, "Filters, sorts and pages the catalog.", &["params"]),
+    ("get_available_inventory", MethodKind::Query, MethodAccess::Read, "Pages through active items with online stock left after reservations and buffers, by name.", &["offset", "limit"]),
+    ("get_sales", MethodKind::Query, MethodAccess::Read, "Retrieves sales history, including voided sales.", &[]),
+    ("get_sale", MethodKind::Query, MethodAccess::Read, "Retrieves one sale by its sequence number, including voided sales.", &["seq"]),
+    ("export_sales_page", MethodKind::Query, MethodAccess::Read, "Pages through sales after a cursor.", &["after_seq", "limit"]),
+    ("sales_ledger", MethodKind::Query, MethodAccess::Read, "One row per sale line within a window, with totals.", &["start", "end"]),
+    ("tax_liability", MethodKind::Query, MethodAccess::Read, "Taxable and exempt sales and tax collected within a window.", &["start", "end"]),
+    ("verify_sale_totals", MethodKind::Query, MethodAccess::Read, "Sales whose stored total disagrees with their lines.", &[]),
+    ("find_sales_by_note", MethodKind::Query, MethodAccess::Read, "Finds sales whose note contains the text.", &["substring"]),
+    ("recent_sales", MethodKind::Query, MethodAccess::Read, "The latest sales, newest first.", &["limit"]),
+    ("sales_today", MethodKind::Query, MethodAccess::Read, "Sales for the current business day.", &[]),
+    ("revenue_today", MethodKind::Query, MethodAccess::Read, "Revenue for the current business day.", &[]),
+    ("sales_heatmap", MethodKind::Query, MethodAccess::Read, "Per-day revenue and sale counts for a calendar month.", &["month_start"]),
+    ("compare_items", MethodKind::Query, MethodAccess::Read, "Side-by-side units sold, revenue and gross profit for two items within a window.", &["a", "b", "start", "end"]),
+    ("item_sales_series", MethodKind::Query, MethodAccess::Read, "Units of one item sold per period across a window.", &["id", "granularity", "start", "end"]),
+    ("detect_anomalies", MethodKind::Query, MethodAccess::Read, "Flags recent sales with outlier totals or deep discounts.", &["lookback_ns"]),
+    ("discount_report", MethodKind::Query, MethodAccess::Read, "Discount value and discounted line count per reason within a window.", &["start", "end"]),
+    ("employee_productivity", MethodKind::Query, MethodAccess::Read, "Revenue per active hour for each cashier within a window.", &["start", "end"]),
+    ("revenue_by_channel", MethodKind::Query, MethodAccess::Read, "Revenue per sales channel within a window.", &["start", "end"]),
+    ("hourly_breakdown", MethodKind::Query, MethodAccess::Read, "Revenue, sales and units per local hour of one day.", &["day_start"]),
+    ("end_of_day_report", MethodKind::Query, MethodAccess::Read, "Close-of-day totals, top sellers, returns and items below reorder point.", &["day_start"]),
+    ("large_sales", MethodKind::Query, MethodAccess::Read, "Lists sales in a window at or above a total, largest first.", &["min_total", "start", "end"]),
+    ("below_cost_sales", MethodKind::Query, MethodAccess::Read, "Sales in a window with lines sold below cost, paired with the loss.", &["start", "end"]),
+    ("sales_containing_item", MethodKind::Query, MethodAccess::Read, "Lists full sales in a window that include an item.", &["id", "start", "end"]),
+    ("item_sales_report", MethodKind::Query, MethodAccess::Read, "Summarizes one item's sales within a window.", &["id", "start", "end"]),
+    ("category_item_breakdown", MethodKind::Query, MethodAccess::Read, "Per-item sales reports for one category within a window.", &["category", "start", "end"]),
+    ("financial_overview", MethodKind::Query, MethodAccess::Read, "Sales revenue and inventory value in the base currency.", &[]),
+    ("profitability", MethodKind::Query, MethodAccess::Read, "Reports gross profit and gross-margin percentage for sales with a known cost.", &[]),
+    ("low_margin_items", MethodKind::Query, MethodAccess::Read, "Lists costed items whose margin is below a percentage, lowest first.", &["min_margin_percent"]),
+    ("inventory_aging", MethodKind::Query, MethodAccess::Read, "Buckets stock by time since each item last sold.", &[]),
+    ("price_distribution", MethodKind::Query, MethodAccess::Read, "Counts catalog items per price band.", &["bucket_size"]),
+    ("inventory_health", MethodKind::Query, MethodAccess::Read, "A 0-100 inventory health score with its components.", &[]),
+    ("items_at_safety_stock", MethodKind::Query, MethodAccess::Read, "Items at or below their safety stock.", &[]),
+    ("reorder_schedule", MethodKind::Query, MethodAccess::Read, "When to reorder each item so stock arrives before it runs out.", &["horizon_ns"]),
+    ("suggest_markdowns", MethodKind::Query, MethodAccess::Read, "Cost-respecting markdowns for items with more cover than `overstock_days`.", &["overstock_days"]),
+    ("clearance_candidates", MethodKind::Query, MethodAccess::Read, "Long-idle overstocked items with a suggested markdown.", &[]),
+    ("unit_economics", MethodKind::Query, MethodAccess::Read, "Unit margin, margin % and markup % for an item; none if it is missing or has no cost.", &["id"]),
+    ("simulate_price_change", MethodKind::Query, MethodAccess::Read, "Compares current and proposed unit margins and projects the revenue and profit change at recent demand.", &["id", "new_price"]),
+    ("item_trend", MethodKind::Query, MethodAccess::Read, "Units sold in the latest window against the one before, classified as rising, falling or flat.", &["id", "window_ns"]),
+    ("target_progress", MethodKind::Query, MethodAccess::Read, "This month's gross profit against the target, with a linear month-end projection.", &[]),
+    ("reorder_suggestions", MethodKind::Query, MethodAccess::Read, "Suggests items to reorder and how many units to order.", &["threshold"]),
+    ("restock_cost_estimate", MethodKind::Query, MethodAccess::Read, "Estimates what replenishing every item below its reorder point will cost.", &[]),
+    ("top_categories", MethodKind::Query, MethodAccess::Read, "Ranks categories by revenue within a window.", &["n", "start", "end"]),
+    ("frequently_bought_together", MethodKind::Query, MethodAccess::Read, "Items most often bought in the same sale as the given item.", &["item_id", "top"]),
+    ("get_top_selling_items", MethodKind::Query, MethodAccess::Read, "Best sellers by units sold.", &["n"]),
+    ("api_descriptor", MethodKind::Query, MethodAccess::Read, "Describes every public method, its kind, access and parameters.", &[]),
+];
+
+#[query(guard = "caller_can_read")]
+fn api_descriptor() -> Vec<MethodDoc> {
+    API_METHODS
+        .iter()
+        .map(|(name, kind, access, summary, params)| MethodDoc {
+            name: name.to_string(),
+            kind: *kind,
+            access: *access,
+            summary: summary.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        })
+        .collect()
+}
+
+ic_cdk::export_candid!();
+
+// Stands in for the system API off-chain so tests can pick the caller and move the clock.
+#[cfg(test)]
+mod test_env {
+    use candid::Principal;
+    use std::cell::Cell;
+
+    pub const CONTROLLER: Principal = Principal::from_slice(&[1]);
+
+    thread_local! {
+        static NOW: Cell<u64> = const { Cell::new(0) };
+        static CALLER: Cell<Principal> = const { Cell::new(CONTROLLER) };
+    }
+
+    pub fn time() -> u64 {
+        NOW.with(Cell::get)
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(Cell::get)
+    }
+
+    pub fn is_controller(principal: &Principal) -> bool {
+        *principal == CONTROLLER
+    }
+
+    pub fn set_time(now: u64) {
+        NOW.with(|cell| cell.set(now));
+    }
+
+    pub fn advance(ns: u64) {
+        NOW.with(|cell| cell.set(cell.get() + ns));
+    }
+
+    pub fn set_caller(principal: Principal) {
+        CALLER.with(|cell| cell.set(principal));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+    use test_env::{advance, set_caller, set_time, CONTROLLER};
+
+    const MINUTE: u64 = NANOS_PER_MINUTE as u64;
+    const HOUR: u64 = NANOS_PER_HOUR as u64;
+    const DAY: u64 = NANOS_PER_DAY as u64;
+    const USER: Principal = Principal::from_slice(&[2]);
+    const OTHER_USER: Principal = Principal::from_slice(&[3]);
+
+    // Canister state is thread-local, but the id counters are process-wide, so tests run one at a time.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn setup() -> MutexGuard<'static, ()> {
+        let guard = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        for counter in [&NEXT_ITEM_ID, &NEXT_SALE_SEQ, &NEXT_RETURN_ID, &NEXT_RESERVATION_ID] {
+            counter.store(1, Ordering::Relaxed);
+        }
+        CATALOG_VERSION.store(0, Ordering::Relaxed);
+        COMPLETED_SALES.store(0, Ordering::Relaxed);
+        set_time(base_time());
+        set_caller(CONTROLLER);
+        guard
+    }
+
+    // Noon UTC on Friday 15 March 2024.
+    fn base_time() -> u64 {
+        days_from_civil(2024, 3, 15) as u64 * DAY + 12 * HOUR
+    }
+
+    fn day_start(year: i64, month: u32, day: u32) -> u64 {
+        days_from_civil(year, month, day) as u64 * DAY
+    }
+
+    fn add(name: &str, quantity: u64, price: f64) -> ItemId {
+        add_item(name.to_string(), quantity, price, None, None, None, None).unwrap()
+    }
+
+    fn add_costed(name: &str, quantity: u64, price: f64, cost: f64) -> ItemId {
+        let id = add(name, quantity, price);
+        set_item_cost(id, Some(cost)).unwrap();
+        id
+    }
+
+    fn add_in_category(name: &str, quantity: u64, price: f64, category: &str) -> ItemId {
+        let id = add(name, quantity, price);
+        set_item_category(id, Some(category.to_string())).unwrap();
+        id
+    }
+
+    fn sell(lines: &[(ItemId, u64)]) -> SaleRecord {
+        record_sale(lines.to_vec(), None).unwrap()
+    }
+
+    fn sell_with(lines: &[(ItemId, u64)], options: SaleOptions) -> SaleRecord {
+        record_sale(lines.to_vec(), Some(options)).unwrap()
+    }
+
+    fn discounted(id: ItemId, quantity: u64, price: f64, reason: DiscountReason) -> SaleLine {
+        SaleLine {
+            override_price: Some(price),
+            discount_reason: Some(reason),
+            ..SaleLine::from((id, quantity))
+        }
+    }
+
+    fn quantity(id: ItemId) -> u64 {
+        get_item_details(id).unwrap().quantity
+    }
+
+    fn seqs(sales: &[SaleRecord]) -> Vec<u64> {
+        sales.iter().map(|sale| sale.seq).collect()
+    }
+
+    fn ids(items: &[InventoryItem]) -> Vec<ItemId> {
+        items.iter().map(|item| item.id).collect()
+    }
+
+    #[test]
+    fn add_item_collapses_whitespace_in_names() {
+        let _env = setup();
+        let id = add("  Coca   Cola  ", 5, 1.5);
+        assert_eq!(get_item_details(id).unwrap().name, "Coca Cola");
+        assert!(matches!(
+            add_item("   ".to_string(), 5, 1.5, None, None, None, None),
+            Err(BusinessError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn quick_sell_sells_one_unit_by_sku() {
+        let _env = setup();
+        let id = add_item("Cola".to_string(), 5, 1.5, Some("COLA-330".to_string()), None, None, None).unwrap();
+        let sale = quick_sell(" COLA-330 ".to_string()).unwrap();
+        assert_eq!(sale.items.len(), 1);
+        assert_eq!(sale.items[0].quantity, 1);
+        assert_eq!(quantity(id), 4);
+        assert_eq!(get_sale_count(), 1);
+        assert!(matches!(quick_sell("MISSING".to_string()), Err(BusinessError::NotFound(_))));
+    }
+
+    #[test]
+    fn reorder_suggestions_order_up_to_target() {
+        let _env = setup();
+        let id = add("Milk", 4, 1.2);
+        add("Bread", 40, 2.0);
+        set_reorder_levels(id, Some(10), Some(25)).unwrap();
+        let suggestions = reorder_suggestions(0);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].item.id, id);
+        assert_eq!(suggestions[0].to_order, 21);
+    }
+
+    #[test]
+    fn listings_are_ordered_deterministically() {
+        let _env = setup();
+        let cherry = add("Cherry", 5, 1.0);
+        let apple = add("Apple", 5, 1.0);
+        let banana = add("Banana", 5, 1.0);
+        sell(&[(cherry, 2), (apple, 2), (banana, 2)]);
+
+        assert_eq!(ids(&get_inventory()), vec![cherry, apple, banana]);
+        assert_eq!(ids(&get_inventory()), ids(&get_inventory()));
+        let expected = vec![("Apple".to_string(), 2), ("Banana".to_string(), 2), ("Cherry".to_string(), 2)];
+        for _ in 0..3 {
+            assert_eq!(get_top_selling_items(10), expected);
+        }
+    }
+
+    #[test]
+    fn zero_quantity_lines_and_empty_baskets_are_rejected() {
+        let _env = setup();
+        let id = add("Tea", 5, 2.0);
+        assert!(matches!(record_sale(vec![(id, 0)], None), Err(BusinessError::ZeroQuantity(_))));
+        assert!(matches!(record_sale(Vec::new(), None), Err(BusinessError::InvalidInput(_))));
+        assert_eq!(quantity(id), 5);
+        assert_eq!(get_sale_count(), 0);
+    }
+
+    #[test]
+    fn large_sales_filters_by_threshold_largest_first() {
+        let _env = setup();
+        let id = add("Widget", 100, 5.0);
+        sell(&[(id, 1)]);
+        let medium = sell(&[(id, 4)]);
+        let large = sell(&[(id, 10)]);
+        let found = large_sales(20.0, 0, time()).unwrap();
+        assert_eq!(seqs(&found), vec![large.seq, medium.seq]);
+    }
+
+    #[test]
+    fn recategorize_moves_uncategorized_items() {
+        let _env = setup();
+        let cola = add("Cola", 5, 1.0);
+        let chips = add("Chips", 5, 1.0);
+        let tea = add_in_category("Tea", 5, 1.0, "Drinks");
+        assert_eq!(recategorize(None, "General".to_string()).unwrap(), 2);
+        for id in [cola, chips] {
+            assert_eq!(get_item_details(id).unwrap().category.as_deref(), Some("General"));
+        }
+        assert_eq!(get_item_details(tea).unwrap().category.as_deref(), Some("Drinks"));
+    }
+
+    #[test]
+    fn profitability_reports_margin_to_two_decimals() {
+        let _env = setup();
+        let id = add_costed("Soap", 10, 3.0, 2.0);
+        sell(&[(id, 3)]);
+        let report = profitability();
+        assert_eq!(report.revenue, 9.0);
+        assert_eq!(report.cost_of_goods, 6.0);
+        assert_eq!(report.gross_margin_percent, 33.33);
+    }
+
+    #[test]
+    fn item_sales_report_aggregates_sales() {
+        let _env = setup();
+        let id = add("Juice", 20, 2.5);
+        let other = add("Water", 20, 1.0);
+        let first = sell(&[(id, 1)]);
+        advance(HOUR);
+        sell(&[(id, 2), (other, 1)]);
+        advance(HOUR);
+        let last = sell(&[(id, 3)]);
+
+        let report = item_sales_report(id, 0, time()).unwrap();
+        assert_eq!(report.units_sold, 6);
+        assert_eq!(report.revenue, 15.0);
+        assert_eq!(report.num_sales, 3);
+        assert_eq!(report.avg_units_per_sale, 2.0);
+        assert_eq!(report.first_sold, Some(first.timestamp));
+        assert_eq!(report.last_sold, Some(last.timestamp));
+    }
+
+    #[test]
+    fn search_items_matches_descriptions() {
+        let _env = setup();
+        let id = add_item(
+            "Red Tea".to_string(),
+            5,
+            3.0,
+            None,
+            Some("Made with organic hibiscus".to_string()),
+            None,
+            None,
+        )
+        .unwrap();
+        add("Green Tea", 5, 3.0);
+        assert_eq!(ids(&search_items("Hibiscus".to_string())), vec![id]);
+        assert!(search_item_by_name("hibiscus".to_string()).is_empty());
+    }
+
+    #[test]
+    fn stock_totals_count_units_and_skus() {
+        let _env = setup();
+        let first = add("A", 2, 1.0);
+        add("B", 3, 1.0);
+        add("C", 5, 1.0);
+        sell(&[(first, 2)]);
+        assert_eq!(inventory_count(), 3);
+        assert_eq!(total_units(), 8);
+        assert_eq!(out_of_stock_count(), 1);
+    }
+
+    #[test]
+    fn sales_get_distinct_increasing_seqs() {
+        let _env = setup();
+        let id = add("Pen", 10, 1.0);
+        let first = sell(&[(id, 1)]);
+        let second = sell(&[(id, 1)]);
+        assert!(second.seq > first.seq);
+        assert_eq!(get_sale(first.seq).unwrap().seq, first.seq);
+        assert_eq!(get_sale(second.seq).unwrap().seq, second.seq);
+    }
+
+    #[test]
+    fn returns_are_broken_down_by_reason() {
+        let _env = setup();
+        let id = add("Kettle", 10, 20.0);
+        let first = sell(&[(id, 1)]);
+        let second = sell(&[(id, 2)]);
+        return_sale_items(first.seq, vec![(id, 1)], ReturnReason::Defective).unwrap();
+        return_sale_items(second.seq, vec![(id, 2)], ReturnReason::WrongItem).unwrap();
+
+        let breakdown = returns_by_reason(0, time()).unwrap();
+        assert_eq!(
+            breakdown,
+            vec![(ReturnReason::WrongItem, 1, 40.0), (ReturnReason::Defective, 1, 20.0)]
+        );
+    }
+
+    #[test]
+    fn stale_quantity_updates_are_rejected() {
+        let _env = setup();
+        let id = add("Rice", 10, 4.0);
+        sell(&[(id, 3)]);
+        assert_eq!(
+            update_quantity_cas(id, 10, 12),
+            Err(BusinessError::ConcurrentModification { current: 7 })
+        );
+        assert_eq!(quantity(id), 7);
+        update_quantity_cas(id, 7, 12).unwrap();
+        assert_eq!(quantity(id), 12);
+    }
+
+    #[test]
+    fn low_margin_items_flags_only_thin_margins() {
+        let _env = setup();
+        add_costed("Healthy", 5, 10.0, 5.0);
+        let thin = add_costed("Thin", 5, 10.0, 9.0);
+        add("Uncosted", 5, 10.0);
+        let flagged = low_margin_items(30.0);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].0.id, thin);
+        assert!((flagged[0].1 - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_updates_are_rejected_without_touching_the_item() {
+        let _env = setup();
+        let id = add("Salt", 5, 1.0);
+        let before = get_item_details(id).unwrap().updated_at;
+        advance(MINUTE);
+        assert_eq!(
+            update_item(id, None, None, None, None, None),
+            Err(BusinessError::NothingToUpdate)
+        );
+        assert_eq!(patch_item(id, ItemPatch::default()), Err(BusinessError::NothingToUpdate));
+        assert_eq!(get_item_details(id).unwrap().updated_at, before);
+    }
+
+    #[test]
+    fn sale_lines_are_capped_and_duplicates_merged() {
+        let _env = setup();
+        let first = add("A", 10, 1.0);
+        let second = add("B", 10, 1.0);
+        let third = add("C", 10, 1.0);
+        set_max_sale_lines(2).unwrap();
+        assert_eq!(
+            record_sale(vec![(first, 1), (second, 1), (third, 1)], None).unwrap_err(),
+            BusinessError::TooManyLines { max: 2 }
+        );
+
+        let sale = sell(&[(first, 1), (first, 2)]);
+        assert_eq!(sale.items.len(), 1);
+        assert_eq!(sale.items[0].quantity, 3);
+        assert_eq!(quantity(first), 7);
+    }
+
+    #[test]
+    fn sales_containing_item_skips_other_sales() {
+        let _env = setup();
+        let target = add("Target", 10, 1.0);
+        let other = add("Other", 10, 1.0);
+        let first = sell(&[(target, 1)]);
+        let second = sell(&[(other, 1), (target, 1)]);
+        sell(&[(other, 1)]);
+        assert_eq!(
+            seqs(&sales_containing_item(target, 0, time()).unwrap()),
+            vec![first.seq, second.seq]
+        );
+    }
+
+    #[test]
+    fn sales_today_starts_at_the_day_boundary() {
+        let _env = setup();
+        let id = add("Bun", 10, 2.0);
+        set_time(base_time() - DAY);
+        sell(&[(id, 1)]);
+        set_time(day_start(2024, 3, 15) + MINUTE);
+        let today = sell(&[(id, 2)]);
+        set_time(base_time());
+        assert_eq!(seqs(&sales_today()), vec![today.seq]);
+        assert_eq!(revenue_today(), 4.0);
+    }
+
+    #[test]
+    fn reservations_are_reclaimed_after_the_ttl() {
+        let _env = setup();
+        let id = add("Lamp", 5, 15.0);
+        set_reservation_ttl(10 * MINUTE).unwrap();
+        reserve_item(id, 3).unwrap();
+        assert_eq!(get_item_availability(id).unwrap().available, 2);
+
+        advance(5 * MINUTE);
+        assert_eq!(expire_stale_reservations().unwrap(), 0);
+        advance(6 * MINUTE);
+        assert_eq!(expire_stale_reservations().unwrap(), 1);
+        assert!(get_reservations().is_empty());
+        assert_eq!(get_item_availability(id).unwrap().available, 5);
+    }
+
+    #[test]
+    fn heartbeat_runs_maintenance_on_its_interval() {
+        let _env = setup();
+        DEAL_SEED.with(|seed| seed.set(Some(7)));
+        let id = add("Lamp", 5, 15.0);
+        heartbeat();
+        reserve_item(id, 3).unwrap();
+
+        advance(35 * MINUTE);
+        heartbeat();
+        assert_eq!(get_reservations().len(), 1);
+
+        advance(30 * MINUTE);
+        heartbeat();
+        assert!(get_reservations().is_empty());
+        assert_eq!(get_item_availability(id).unwrap().available, 5);
+    }
+
+    #[test]
+    fn search_combines_filters_with_paging() {
+        let _env = setup();
+        set_allow_zero_initial_stock(true).unwrap();
+        let juice = add_in_category("Apple Juice", 5, 3.0, "Drinks");
+        let soda = add_in_category("Apple Soda", 3, 2.0, "Drinks");
+        add_in_category("Apple Cider", 0, 4.0, "Drinks");
+        add_in_category("Apple Pie", 4, 6.0, "Bakery");
+        add_in_category("Orange Juice", 4, 3.0, "Drinks");
+
+        let params = |offset| SearchParams {
+            text: Some("apple".to_string()),
+            category: Some("Drinks".to_string()),
+            tags: Vec::new(),
+            in_stock_only: true,
+            include_inactive: None,
+            sort: SortField::Name,
+            ascending: true,
+            offset,
+            limit: 1,
+        };
+        let first = search(params(0)).unwrap();
+        assert_eq!(first.total, 2);
+        assert_eq!(ids(&first.items), vec![juice]);
+        let second = search(params(1)).unwrap();
+        assert_eq!(second.total, 2);
+        assert_eq!(ids(&second.items), vec![soda]);
+    }
+
+    #[test]
+    fn override_prices_are_recorded_on_the_line_only() {
+        let _env = setup();
+        let id = add("Chair", 5, 50.0);
+        let sale = record_sale_lines(vec![discounted(id, 2, 40.0, DiscountReason::PriceMatch)], None).unwrap();
+        assert_eq!(sale.items[0].unit_price, 40.0);
+        assert_eq!(sale.items[0].list_price, 50.0);
+        assert_eq!(sale.total_amount, 80.0);
+        assert_eq!(get_item_details(id).unwrap().price, 50.0);
+    }
+
+    #[test]
+    fn restock_cost_estimate_sums_items_below_reorder_point() {
+        let _env = setup();
+        let first = add_costed("Flour", 2, 3.0, 1.5);
+        let second = add_costed("Sugar", 1, 4.0, 2.0);
+        let stocked = add_costed("Oil", 20, 6.0, 4.0);
+        set_reorder_levels(first, Some(5), Some(10)).unwrap();
+        set_reorder_levels(second, Some(4), None).unwrap();
+        set_reorder_levels(stocked, Some(5), None).unwrap();
+
+        let estimate = restock_cost_estimate();
+        assert_eq!(estimate.total_cost, 8.0 * 1.5 + 7.0 * 2.0);
+        assert!(estimate.uncosted_items.is_empty());
+    }
+
+    #[test]
+    fn inverted_windows_are_rejected_everywhere() {
+        let _env = setup();
+        let id = add("Item", 5, 1.0);
+        let (start, end) = (time(), time() - HOUR);
+        let inverted = |result: Result<(), BusinessError>| matches!(result, Err(BusinessError::InvalidTimeRange(_)));
+        assert!(inverted(sales_ledger(start, end).map(drop)));
+        assert!(inverted(tax_liability(start, end).map(drop)));
+        assert!(inverted(large_sales(0.0, start, end).map(drop)));
+        assert!(inverted(sales_containing_item(id, start, end).map(drop)));
+        assert!(inverted(item_sales_report(id, start, end).map(drop)));
+        assert!(inverted(top_categories(5, start, end).map(drop)));
+        assert!(inverted(returns_by_reason(start, end).map(drop)));
+        assert!(inverted(revenue_by_channel(start, end).map(drop)));
+        assert!(inverted(stock_ledger(id, start, end).map(drop)));
+    }
+
+    #[test]
+    fn merge_items_combines_stock_and_history() {
+        let _env = setup();
+        let keep = add("Cola", 3, 1.0);
+        let merge = add_item("Cola".to_string(), 2, 1.0, None, None, None, Some("Can".to_string())).unwrap();
+        let sale = sell(&[(merge, 1)]);
+        set_featured(vec![merge, keep]).unwrap();
+        set_max_stock(keep, Some(3)).unwrap();
+        assert!(matches!(
+            merge_items(keep, merge),
+            Err(BusinessError::ExceedsCapacity { max: 3, attempted: 4 })
+        ));
+        assert_eq!(quantity(merge), 1);
+        set_max_stock(keep, Some(4)).unwrap();
+        merge_items(keep, merge).unwrap();
+
+        assert_eq!(quantity(keep), 4);
+        assert_eq!(get_config().featured_items, vec![keep]);
+        assert!(matches!(get_item_details(merge), Err(BusinessError::NotFound(_))));
+        assert_eq!(seqs(&sales_containing_item(keep, 0, time()).unwrap()), vec![sale.seq]);
+        assert_eq!(item_sales_report(keep, 0, time()).unwrap().units_sold, 1);
+    }
+
+    #[test]
+    fn unit_economics_reports_margin_and_markup() {
+        let _env = setup();
+        let id = add_costed("Mug", 5, 10.0, 6.0);
+        let economics = unit_economics(id).unwrap();
+        assert_eq!(economics.unit_margin, 4.0);
+        assert!((economics.margin_percent - 40.0).abs() < 1e-9);
+        assert!((economics.markup_percent - 66.666_666).abs() < 1e-3);
+
+        let uncosted = add("Plate", 5, 10.0);
+        assert!(unit_economics(uncosted).is_none());
+        assert!(unit_economics(999).is_none());
+    }
+
+    #[test]
+    fn restocks_beyond_capacity_are_rejected() {
+        let _env = setup();
+        let id = add("Shelf Item", 6, 2.0);
+        set_max_stock(id, Some(10)).unwrap();
+        assert_eq!(
+            restock_item(id, 5, None),
+            Err(BusinessError::ExceedsCapacity { max: 10, attempted: 11 })
+        );
+        assert_eq!(quantity(id), 6);
+        assert_eq!(restock_item(id, 4, None).unwrap(), 10);
+    }
+
+    #[test]
+    fn top_categories_rank_by_revenue() {
+        let _env = setup();
+        let cola = add_in_category("Cola", 20, 3.0, "Drinks");
+        let chips = add_in_category("Chips", 20, 2.0, "Snacks");
+        let plain = add("Loose", 20, 1.0);
+        sell(&[(cola, 10), (chips, 5), (plain, 1)]);
+
+        let ranking = top_categories(2, 0, time()).unwrap();
+        assert_eq!(
+            ranking,
+            vec![("Drinks".to_string(), 30.0, 10), ("Snacks".to_string(), 10.0, 5)]
+        );
+    }
+
+    #[test]
+    fn financial_overview_rounds_float_artifacts() {
+        let _env = setup();
+        let dime = add("Dime", 3, 0.1);
+        let other = add("Double Dime", 1, 0.2);
+        sell(&[(dime, 1)]);
+        sell(&[(other, 1)]);
+        assert_eq!(financial_overview(), (0.3, 0.2));
+    }
+
+    #[test]
+    fn inventory_aging_buckets_by_last_sale() {
+        let _env = setup();
+        set_time(base_time() - 100 * DAY);
+        let old = add("Old", 5, 1.0);
+        sell(&[(old, 1)]);
+        set_time(base_time() - 45 * DAY);
+        add("Unsold", 5, 1.0);
+        set_time(base_time() - 10 * DAY);
+        let fresh = add("Fresh", 5, 1.0);
+        sell(&[(fresh, 1)]);
+        set_time(base_time());
+
+        let counts: Vec<(u64, Option<u64>, u64)> = inventory_aging()
+            .iter()
+            .map(|bucket| (bucket.min_days, bucket.max_days, bucket.item_count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![(0, Some(30), 1), (31, Some(60), 1), (61, Some(90), 0), (91, None, 1)]
+        );
+    }
+
+    #[test]
+    fn price_list_dry_run_matches_the_real_apply() {
+        let _env = setup();
+        let raised = add_costed("Raised", 5, 10.0, 5.0);
+        let invalid = add_costed("Invalid", 5, 10.0, 5.0);
+        let changes = vec![(raised, 12.0), (invalid, -1.0), (999, 3.0)];
+        let summary = |report: &PriceListReport| {
+            report
+                .outcomes
+                .iter()
+                .map(|outcome| (outcome.id, outcome.applied_price, outcome.rejection.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let preview = apply_price_list(changes.clone(), true).unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(get_item_details(raised).unwrap().price, 10.0);
+        let applied = apply_price_list(changes, false).unwrap();
+        assert_eq!(summary(&preview), summary(&applied));
+        assert_eq!((applied.applied, applied.rejected), (1, 2));
+        assert_eq!(get_item_details(raised).unwrap().price, 12.0);
+        assert_eq!(get_item_details(invalid).unwrap().price, 10.0);
+    }
+
+    #[test]
+    fn price_list_reports_the_capped_price() {
+        let _env = setup();
+        let id = add_costed("Capped", 5, 12.0, 5.0);
+        set_min_margin(Some(50.0), MarginPolicy::Cap).unwrap();
+        let report = apply_price_list(vec![(id, 8.0)], false).unwrap();
+        assert_eq!(report.outcomes[0].new_price, 8.0);
+        assert_eq!(report.outcomes[0].applied_price, Some(10.0));
+        assert_eq!(get_item_details(id).unwrap().price, 10.0);
+    }
+
+    #[test]
+    fn anonymous_callers_cannot_update() {
+        let _env = setup();
+        set_caller(Principal::anonymous());
+        assert!(caller_is_authenticated().is_err());
+        set_caller(USER);
+        assert!(caller_is_authenticated().is_ok());
+        assert!(add_item("Gum".to_string(), 5, 0.5, None, None, None, None).is_ok());
+    }
+
+    #[test]
+    fn sales_heatmap_covers_the_whole_month() {
+        let _env = setup();
+        let id = add("Scarf", 50, 10.0);
+        set_time(day_start(2024, 4, 3) + 9 * HOUR);
+        sell(&[(id, 1)]);
+        set_time(day_start(2024, 4, 3) + 15 * HOUR);
+        sell(&[(id, 2)]);
+        set_time(day_start(2024, 4, 30) + 10 * HOUR);
+        sell(&[(id, 4)]);
+
+        let heatmap = sales_heatmap(day_start(2024, 4, 1));
+        assert_eq!(heatmap.len(), 30);
+        assert_eq!((heatmap[2].day, heatmap[2].revenue, heatmap[2].num_sales), (3, 30.0, 2));
+        assert_eq!((heatmap[29].day, heatmap[29].revenue, heatmap[29].num_sales), (30, 40.0, 1));
+        assert_eq!(heatmap.iter().map(|day| day.num_sales).sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn sales_heatmap_applies_the_day_cutoff() {
+        let _env = setup();
+        let id = add("Scarf", 50, 10.0);
+        set_day_cutoff_hour(4).unwrap();
+        set_time(day_start(2024, 4, 1) + 2 * HOUR);
+        sell(&[(id, 1)]);
+        set_time(day_start(2024, 4, 1) + 5 * HOUR);
+        sell(&[(id, 2)]);
+
+        let march = sales_heatmap(day_start(2024, 3, 15));
+        assert_eq!(march.len(), 31);
+        assert_eq!(march[30].num_sales, 1);
+        let april = sales_heatmap(day_start(2024, 4, 15));
+        assert_eq!(april[0].num_sales, 1);
+        assert_eq!(april[0].revenue, 20.0);
+    }
+
+    #[test]
+    fn huge_result_sizes_are_capped() {
+        let _env = setup();
+        let ids: Vec<ItemId> = (0..MAX_TOP_N as u64 + 5).map(|n| add(&format!("Item {}", n), 1, 1.0)).collect();
+        for chunk in ids.chunks(DEFAULT_MAX_SALE_LINES as usize) {
+            sell(&chunk.iter().map(|id| (*id, 1)).collect::<Vec<_>>());
+        }
+        assert_eq!(get_top_selling_items(usize::MAX).len(), MAX_TOP_N);
+        assert_eq!(search_ranked("item".to_string(), usize::MAX).len(), MAX_PAGE_SIZE as usize);
+    }
+
+    #[test]
+    fn reconciliation_flags_a_corrupted_quantity() {
+        let _env = setup();
+        let id = add("Bolt", 10, 0.5);
+        sell(&[(id, 3)]);
+        restock_item(id, 5, None).unwrap();
+        assert!(reconcile_item(id).unwrap().consistent);
+
+        INVENTORY.with(|inventory| inventory.borrow_mut().get_mut(&id).unwrap().quantity = 20);
+        let report = reconcile_item(id).unwrap();
+        assert!(!report.consistent);
+        assert_eq!(report.expected_quantity, 12);
+        assert_eq!(report.discrepancy, 8);
+    }
+
+    #[test]
+    fn check_availability_flags_each_line() {
+        let _env = setup();
+        let plenty = add("Plenty", 5, 1.0);
+        let short = add("Short", 3, 1.0);
+        let lines = check_availability(vec![(plenty, 2), (short, 10)]);
+        let flags: Vec<(ItemId, u64, bool)> = lines.iter().map(|line| (line.id, line.available, line.sufficient)).collect();
+        assert_eq!(flags, vec![(plenty, 5, true), (short, 3, false)]);
+    }
+
+    #[test]
+    fn late_night_sales_belong_to_the_previous_business_day() {
+        let _env = setup();
+        let id = add("Nightcap", 10, 8.0);
+        set_day_cutoff_hour(4).unwrap();
+        set_time(day_start(2024, 3, 15) + 2 * HOUR);
+        sell(&[(id, 1)]);
+        set_time(base_time());
+        assert_eq!(end_of_day_report(day_start(2024, 3, 14) + 12 * HOUR).num_sales, 1);
+        assert_eq!(end_of_day_report(base_time()).num_sales, 0);
+        assert!(sales_today().is_empty());
+    }
+
+    #[test]
+    fn export_pages_neither_overlap_nor_skip() {
+        let _env = setup();
+        let id = add("Card", 20, 1.0);
+        for _ in 0..5 {
+            sell(&[(id, 1)]);
+        }
+        let page_seqs = |page: &SalesExportPage| -> Vec<u64> {
+            let rows: Vec<serde_json::Value> = serde_json::from_str(&page.json).unwrap();
+            rows.iter().map(|row| row["seq"].as_u64().unwrap()).collect()
+        };
+
+        let first = export_sales_page(None, 3).unwrap();
+        assert_eq!((first.count, first.next_cursor), (3, Some(3)));
+        let second = export_sales_page(first.next_cursor, 3).unwrap();
+        assert_eq!((second.count, second.next_cursor), (2, None));
+        let mut all = page_seqs(&first);
+        all.extend(page_seqs(&second));
+        assert_eq!(all, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn items_inherit_the_category_reorder_threshold() {
+        let _env = setup();
+        let inherits = add_in_category("Cola", 4, 1.0, "Drinks");
+        let own = add_in_category("Tea", 4, 1.0, "Drinks");
+        set_reorder_levels(own, Some(2), None).unwrap();
+        add_in_category("Chips", 4, 1.0, "Snacks");
+        set_category_reorder_threshold("Drinks".to_string(), Some(10)).unwrap();
+
+        let suggestions = reorder_suggestions(0);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].item.id, inherits);
+        assert_eq!(suggestions[0].to_order, 16);
+    }
+
+    #[test]
+    fn sales_can_be_amended_only_inside_the_window() {
+        let _env = setup();
+        let first = add("Pasta", 10, 2.0);
+        let second = add("Sauce", 10, 3.0);
+        let sale = sell(&[(first, 2)]);
+
+        let amended = amend_sale(sale.seq, vec![(first, 3), (second, 1)]).unwrap();
+        assert_eq!(amended.total_amount, 9.0);
+        assert_eq!((quantity(first), quantity(second)), (7, 9));
+
+        advance(DEFAULT_SALE_AMEND_WINDOW_NS + MINUTE);
+        assert!(matches!(amend_sale(sale.seq, vec![(first, 1)]), Err(BusinessError::Conflict(_))));
+        assert_eq!(quantity(first), 7);
+    }
+
+    #[test]
+    fn amending_keeps_line_terms_and_runs_sale_checks() {
+        let _env = setup();
+        let id = add("Lamp", 10, 10.0);
+        let sale = record_sale_lines(vec![discounted(id, 2, 8.0, DiscountReason::Promotion)], None).unwrap();
+
+        let amended = amend_sale(sale.seq, vec![(id, 3)]).unwrap();
+        assert_eq!(amended.items[0].unit_price, 8.0);
+        assert_eq!(amended.items[0].discount_reason, Some(DiscountReason::Promotion));
+        assert_eq!(amended.total_amount, 24.0);
+        assert!(stock_ledger(id, 0, time())
+            .unwrap()
+            .iter()
+            .any(|entry| entry.kind == MovementKind::Adjustment && entry.ref_id == Some(sale.seq)));
+
+        set_min_sale_amount(20.0).unwrap();
+        assert!(matches!(
+            amend_sale(sale.seq, vec![(id, 1)]),
+            Err(BusinessError::BelowMinimumSale { .. })
+        ));
+        assert_eq!(quantity(id), 7);
+    }
+
+    #[test]
+    fn target_progress_projects_the_month_end() {
+        let _env = setup();
+        set_monthly_target(100.0).unwrap();
+        let id = add_costed("Vase", 10, 10.0, 5.0);
+        sell(&[(id, 4)]);
+
+        let progress = target_progress();
+        assert_eq!(progress.achieved, 20.0);
+        assert_eq!(progress.percent, 20.0);
+        assert_eq!(progress.projected_month_end, 42.76);
+    }
+
+    #[test]
+    fn bulk_restock_reports_each_line() {
+        let _env = setup();
+        let first = add("A", 5, 1.0);
+        let capped = add("B", 5, 1.0);
+        let third = add("C", 5, 1.0);
+        set_max_stock(capped, Some(8)).unwrap();
+
+        let results = bulk_restock(vec![(first, 5), (capped, 5), (third, 1)]).unwrap();
+        assert_eq!(
+            results,
+            vec![Ok(10), Err(BusinessError::ExceedsCapacity { max: 8, attempted: 10 }), Ok(6)]
+        );
+        assert_eq!(quantity(capped), 5);
+    }
+
+    #[test]
+    fn anonymous_reads_follow_the_toggle() {
+        let _env = setup();
+        set_caller(Principal::anonymous());
+        assert!(caller_can_read().is_ok());
+
+        set_caller(CONTROLLER);
+        set_allow_public_read(false).unwrap();
+        set_caller(Principal::anonymous());
+        assert!(caller_can_read().is_err());
+        set_caller(USER);
+        assert!(caller_can_read().is_ok());
+        assert!(matches!(set_allow_public_read(true), Err(BusinessError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn maintenance_interval_is_bounded() {
+        let _env = setup();
+        assert!(matches!(set_maintenance_interval(0), Err(BusinessError::InvalidInput(_))));
+        assert!(matches!(
+            set_maintenance_interval(MAX_MAINTENANCE_INTERVAL_SECS + 1),
+            Err(BusinessError::InvalidInput(_))
+        ));
+        set_maintenance_interval(MAX_MAINTENANCE_INTERVAL_SECS).unwrap();
+        assert_eq!(
+            CONFIG.with(|config| config.borrow().maintenance_interval_ns),
+            Some(MAX_MAINTENANCE_INTERVAL_SECS * NANOS_PER_SECOND)
+        );
+    }
+
+    #[test]
+    fn owner_only_settings_reject_other_callers() {
+        let _env = setup();
+        set_caller(USER);
+        let unauthorized = |result: ApiResult<()>| matches!(result, Err(BusinessError::Unauthorized(_)));
+        assert!(unauthorized(set_currency("EUR".to_string(), 2)));
+        assert!(unauthorized(set_value_caps(10.0, 10)));
+        assert!(unauthorized(set_rate_limit(1, 1)));
+        assert!(unauthorized(set_tax_rate(5.0)));
+        assert!(unauthorized(set_min_margin(Some(10.0), MarginPolicy::Reject)));
+        assert!(unauthorized(set_snapshot_interval(Some(DAY))));
+        assert!(unauthorized(take_snapshot().map(drop)));
+        assert!(unauthorized(rollback_to_snapshot(1)));
+        assert!(unauthorized(set_featured(Vec::new())));
+        assert!(unauthorized(disable_maintenance()));
+        assert!(unauthorized(set_markdown_min_margin(5.0)));
+        assert!(unauthorized(set_max_sale_lines(10)));
+        assert!(unauthorized(set_maintenance_interval(60)));
+        assert!(unauthorized(set_min_sale_amount(5.0)));
+        assert!(unauthorized(set_exchange_rate("EUR".to_string(), 1.1)));
+    }
+
+    #[test]
+    fn ranked_search_favours_popular_items() {
+        let _env = setup();
+        let classic = add("Cola Classic", 20, 1.0);
+        let zero = add("Cola Zero", 20, 1.0);
+        sell(&[(classic, 1)]);
+        sell(&[(zero, 5)]);
+        assert_eq!(ids(&search_ranked("cola".to_string(), 10)), vec![zero, classic]);
+    }
+
+    #[test]
+    fn sales_store_profit_at_sale_time() {
+        let _env = setup();
+        let first = add_costed("A", 10, 10.0, 6.0);
+        let second = add_costed("B", 10, 5.0, 2.0);
+        let sale = sell(&[(first, 2), (second, 1)]);
+        assert_eq!(sale.profit, 11.0);
+        set_item_cost(first, Some(1.0)).unwrap();
+        assert_eq!(get_sale(sale.seq).unwrap().profit, 11.0);
+    }
+
+    #[test]
+    fn inventory_health_combines_its_components() {
+        let _env = setup();
+        set_allow_zero_initial_stock(true).unwrap();
+        set_time(base_time() - 100 * DAY);
+        add("Dead", 10, 1.0);
+        set_time(base_time());
+        add("Fresh", 10, 1.0);
+        let low = add("Low", 5, 1.0);
+        set_reorder_levels(low, Some(6), None).unwrap();
+        add("Empty", 0, 1.0);
+
+        let health = inventory_health();
+        assert_eq!(health.in_stock_percent, 75.0);
+        assert_eq!(health.below_reorder_percent, 25.0);
+        assert_eq!(health.dead_stock_percent, 25.0);
+        assert_eq!(health.avg_days_of_cover, None);
+        assert!((health.score - 75.0).abs() < 1e-9);
+        assert!((0.0..=100.0).contains(&health.score));
+    }
+
+    #[test]
+    fn foreign_currency_sales_use_the_configured_rate() {
+        let _env = setup();
+        let id = add("Tour", 10, 10.0);
+        set_exchange_rate("EUR".to_string(), 1.25).unwrap();
+        let sale = sell_with(
+            &[(id, 2)],
+            SaleOptions {
+                currency: Some("eur".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(sale.total_amount, 20.0);
+        let foreign = sale.foreign_total.unwrap();
+        assert_eq!((foreign.code.as_str(), foreign.rate_to_base, foreign.amount), ("EUR", 1.25, 16.0));
+
+        let unknown = SaleOptions {
+            currency: Some("GBP".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(record_sale(vec![(id, 1)], Some(unknown)), Err(BusinessError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn tax_applies_only_to_taxable_lines() {
+        let _env = setup();
+        set_tax_rate(10.0).unwrap();
+        let taxable = add("Taxed", 10, 10.0);
+        let exempt = add("Exempt", 10, 5.0);
+        let sale = record_sale_lines(
+            vec![
+                SaleLine::from((taxable, 1)),
+                SaleLine {
+                    tax_exempt: Some(true),
+                    ..SaleLine::from((exempt, 2))
+                },
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(sale.tax_amount, 1.0);
+        assert_eq!(sale.total_amount, 20.0);
+    }
+
+    #[test]
+    fn sales_series_includes_empty_buckets() {
+        let _env = setup();
+        let id = add("Candle", 10, 4.0);
+        let start = day_start(2024, 3, 13);
+        set_time(start + 10 * HOUR);
+        sell(&[(id, 2)]);
+        set_time(base_time());
+        sell(&[(id, 3)]);
+
+        let series = item_sales_series(id, Granularity::Day, start, time()).unwrap();
+        assert_eq!(series, vec![(start, 2), (start + DAY, 0), (start + 2 * DAY, 3)]);
+    }
+
+    #[test]
+    fn repeated_client_keys_create_one_item() {
+        let _env = setup();
+        let key = Some("order-42".to_string());
+        let first = add_item("Kite".to_string(), 5, 9.0, None, None, key.clone(), None).unwrap();
+        let second = add_item("Kite".to_string(), 5, 9.0, None, None, key, None).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(inventory_count(), 1);
+        assert_eq!(quantity(first), 5);
+    }
+
+    #[test]
+    fn clearance_markdowns_respect_cost() {
+        let _env = setup();
+        set_time(base_time() - 120 * DAY);
+        let idle = add_costed("Idle", 100, 10.0, 4.0);
+        set_time(base_time() - 100 * DAY);
+        sell(&[(idle, 1)]);
+        set_time(base_time());
+        let recent = add_costed("Recent", 100, 10.0, 4.0);
+        sell(&[(recent, 1)]);
+
+        let candidates = clearance_candidates();
+        assert_eq!(candidates.len(), 1);
+        let candidate = &candidates[0];
+        assert_eq!(candidate.item.id, idle);
+        assert_eq!(candidate.days_since_last_sale, 100);
+        assert!(candidate.markdown_percent > 0.0);
+        assert!(candidate.suggested_price >= 4.0);
+    }
+
+    #[test]
+    fn absurd_prices_and_quantities_are_rejected() {
+        let _env = setup();
+        assert!(matches!(
+            add_item("Gold".to_string(), 1, 1e12, None, None, None, None),
+            Err(BusinessError::ValueTooLarge(_))
+        ));
+        assert!(matches!(
+            add_item("Sand".to_string(), 10_000_000_000_000, 1.0, None, None, None, None),
+            Err(BusinessError::ValueTooLarge(_))
+        ));
+        let id = add("Sand", 1000, 1.0);
+        assert!(matches!(restock_item(id, u64::MAX / 2, None), Err(BusinessError::ValueTooLarge(_))));
+        assert_eq!(inventory_count(), 1);
+    }
+
+    #[test]
+    fn end_of_day_report_fills_every_section() {
+        let _env = setup();
+        set_tax_rate(10.0).unwrap();
+        let id = add_costed("Bagel", 6, 2.0, 1.0);
+        set_reorder_levels(id, Some(5), None).unwrap();
+        sell(&[(id, 2)]);
+        let sale = sell(&[(id, 1)]);
+        return_sale_items(sale.seq, vec![(id, 1)], ReturnReason::CustomerChangedMind).unwrap();
+        sell(&[(id, 2)]);
+
+        let report = end_of_day_report(base_time());
+        assert_eq!(report.num_sales, 3);
+        assert_eq!(report.units_sold, 5);
+        assert_eq!(report.revenue, 10.0);
+        assert_eq!(report.tax_collected, 1.0);
+        assert_eq!(report.profit, 4.0);
+        assert_eq!(report.top_sellers, vec![("Bagel".to_string(), 5)]);
+        assert_eq!(report.returns.len(), 1);
+        assert_eq!(report.refunded, 2.0);
+        assert_eq!(report.low_stock.len(), 1);
+    }
+
+    #[test]
+    fn inactive_items_stay_listed_but_cannot_be_sold() {
+        let _env = setup();
+        let id = add("Retired", 5, 3.0);
+        set_item_active(id, false).unwrap();
+        assert!(matches!(record_sale(vec![(id, 1)], None), Err(BusinessError::InvalidInput(_))));
+        assert_eq!(ids(&get_inventory()), vec![id]);
+        assert_eq!(quantity(id), 5);
+    }
+
+    #[test]
+    fn recent_sales_are_newest_first_and_capped() {
+        let _env = setup();
+        let id = add("Coffee", 10, 3.0);
+        for _ in 0..3 {
+            sell(&[(id, 1)]);
+            advance(MINUTE);
+        }
+        assert_eq!(seqs(&recent_sales(2)), vec![3, 2]);
+    }
+
+    #[test]
+    fn tag_items_tags_every_match() {
+        let _env = setup();
+        let cola = add_in_category("Cola", 5, 1.0, "Drinks");
+        let tea = add_in_category("Tea", 5, 1.0, "Drinks");
+        let chips = add_in_category("Chips", 5, 1.0, "Snacks");
+        let filter = InventoryFilter {
+            text: None,
+            category: Some("Drinks".to_string()),
+            tags: Vec::new(),
+            in_stock_only: false,
+            include_inactive: false,
+        };
+        assert_eq!(tag_items(filter, "summer".to_string()).unwrap(), 2);
+        for id in [cola, tea] {
+            assert_eq!(get_item_details(id).unwrap().tags, vec!["summer".to_string()]);
+        }
+        assert!(get_item_details(chips).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn top_sellers_keep_deleted_items() {
+        let _env = setup();
+        let id = add("Old Widget", 10, 4.0);
+        sell(&[(id, 3)]);
+        remove_item(id).unwrap();
+        assert_eq!(get_top_selling_items(5), vec![("Old Widget".to_string(), 3)]);
+    }
+
+    #[test]
+    fn updates_are_rate_limited_after_the_allotment() {
+        let _env = setup();
+        set_rate_limit(3, 1).unwrap();
+        set_caller(USER);
+        for _ in 0..3 {
+            set_default_reorder_point(1).unwrap();
+        }
+        match set_default_reorder_point(1) {
+            Err(BusinessError::RateLimited { retry_after }) => assert!((1..=60).contains(&retry_after)),
+            other => panic!("expected a rate limit, got {:?}", other),
+        }
+        set_caller(OTHER_USER);
+        assert!(set_default_reorder_point(1).is_ok());
+
+        set_caller(USER);
+        advance(60 * NANOS_PER_SECOND);
+        assert!(set_default_reorder_point(1).is_ok());
+        set_caller(CONTROLLER);
+        for _ in 0..10 {
+            assert!(set_default_reorder_point(1).is_ok());
+        }
+    }
+
+    #[test]
+    fn compare_items_reports_both_sides() {
+        let _env = setup();
+        let first = add_costed("Espresso", 10, 10.0, 6.0);
+        let second = add("Latte", 10, 5.0);
+        sell(&[(first, 3)]);
+        sell(&[(second, 1)]);
+
+        let comparison = compare_items(first, second, 0, time()).unwrap();
+        assert_eq!((comparison.a.units_sold, comparison.a.revenue, comparison.a.profit), (3, 30.0, 12.0));
+        assert_eq!((comparison.b.units_sold, comparison.b.revenue, comparison.b.profit), (1, 5.0, 0.0));
+        assert_eq!(comparison.b.name.as_deref(), Some("Latte"));
+    }
+
+    #[test]
+    fn state_survives_a_simulated_upgrade() {
+        let _env = setup();
+        let id = add("Drum", 10, 30.0);
+        let sale = sell(&[(id, 2)]);
+        assert_eq!(get_sale(sale.seq).unwrap().total_amount, 60.0);
+
+        pre_upgrade();
+        INVENTORY.with(|inventory| inventory.borrow_mut().clear());
+        SALES.with(|sales| *sales.borrow_mut() = SaleStore::init());
+        NEXT_SALE_SEQ.store(1, Ordering::Relaxed);
+        restore_state(read_state(UPGRADE_MEMORY_ID));
+
+        assert_eq!(quantity(id), 8);
+        assert_eq!(get_sale(sale.seq).unwrap().items[0].quantity, 2);
+        assert_eq!(sell(&[(id, 1)]).seq, sale.seq + 1);
+    }
+
+    #[test]
+    fn reorder_schedule_accounts_for_lead_time() {
+        let _env = setup();
+        let id = add("Filter", 50, 5.0);
+        set_item_lead_time(id, Some(5)).unwrap();
+        set_time(base_time() - 10 * DAY);
+        sell(&[(id, 30)]);
+        set_time(base_time());
+
+        let schedule = reorder_schedule(30 * DAY);
+        assert_eq!(schedule.len(), 1);
+        assert_eq!(schedule[0].stockout_at, base_time() + 20 * DAY);
+        assert_eq!(schedule[0].order_by, base_time() + 15 * DAY);
+        assert!(reorder_schedule(10 * DAY).is_empty());
+    }
+
+    #[test]
+    fn weighted_average_cost_blends_restocks() {
+        let _env = setup();
+        set_valuation_method(ValuationMethod::WeightedAverage).unwrap();
+        let id = add_costed("Screws", 10, 5.0, 2.0);
+        restock_item(id, 10, Some(4.0)).unwrap();
+        let cost = get_item_details(id).unwrap().cost_price.unwrap();
+        assert!(cost > 2.0 && cost < 4.0);
+        assert!((cost - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn category_breakdown_lists_items_by_revenue() {
+        let _env = setup();
+        let cola = add_in_category("Cola", 20, 1.0, "Drinks");
+        let juice = add_in_category("Juice", 20, 3.0, "Drinks");
+        let idle = add_in_category("Water", 20, 1.0, "Drinks");
+        let chips = add_in_category("Chips", 20, 2.0, "Snacks");
+        sell(&[(cola, 10), (juice, 5), (chips, 1)]);
+
+        let breakdown = category_item_breakdown("drinks".to_string(), 0, time()).unwrap();
+        let rows: Vec<(ItemId, u64, f64)> = breakdown.iter().map(|row| (row.id, row.units_sold, row.revenue)).collect();
+        assert_eq!(rows, vec![(juice, 5, 15.0), (cola, 10, 10.0), (idle, 0, 0.0)]);
+    }
+
+    #[test]
+    fn counts_track_inserts_deletes_and_settled_sales() {
+        let _env = setup();
+        let first = add("A", 10, 1.0);
+        let second = add("B", 10, 1.0);
+        add("C", 10, 1.0);
+        remove_item(second).unwrap();
+        assert_eq!(get_item_count(), 2);
+
+        sell(&[(first, 1)]);
+        let voided = sell(&[(first, 1)]);
+        void_sale(voided.seq, "Rung up twice".to_string()).unwrap();
+        sell_with(
+            &[(first, 1)],
+            SaleOptions {
+                pending: Some(true),
+                ..Default::default()
+            },
+        );
+        assert_eq!(get_sale_count(), 1);
+    }
+
+    #[test]
+    fn featured_items_skip_deleted_ones() {
+        let _env = setup();
+        let first = add("A", 5, 1.0);
+        let second = add("B", 5, 1.0);
+        let third = add("C", 5, 1.0);
+        set_featured(vec![third, first, second]).unwrap();
+        remove_item(first).unwrap();
+        assert_eq!(ids(&get_featured()), vec![third, second]);
+        assert_eq!(get_config().featured_items, vec![third, second]);
+    }
+
+    #[test]
+    fn anomalies_flag_the_outlier() {
+        let _env = setup();
+        let id = add("Unit", 1000, 1.0);
+        for units in [10, 12, 11, 9, 10] {
+            sell(&[(id, units)]);
+        }
+        let outlier = sell(&[(id, 500)]);
+
+        let anomalies = detect_anomalies(DAY);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].sale.seq, outlier.seq);
+        assert!(matches!(anomalies[0].reasons[..], [AnomalyReason::HighTotal { .. }]));
+    }
+
+    #[test]
+    fn safety_stock_blocks_sales_without_an_override() {
+        let _env = setup();
+        let id = add("Insulin", 5, 20.0);
+        set_safety_stock(id, Some(3)).unwrap();
+        sell(&[(id, 2)]);
+        assert!(matches!(record_sale(vec![(id, 1)], None), Err(BusinessError::InsufficientStock(_))));
+
+        let overridden = SaleLine {
+            override_safety_stock: Some(true),
+            ..SaleLine::from((id, 1))
+        };
+        record_sale_lines(vec![overridden], None).unwrap();
+        assert_eq!(quantity(id), 2);
+    }
+
+    #[test]
+    fn sales_ledger_flattens_lines_with_totals() {
+        let _env = setup();
+        let first = add("A", 20, 10.0);
+        let second = add("B", 20, 5.0);
+        sell(&[(first, 2)]);
+        sell(&[(first, 1), (second, 3)]);
+
+        let ledger = sales_ledger(0, time()).unwrap();
+        let rows: Vec<(u64, ItemId, u64, f64)> = ledger
+            .entries
+            .iter()
+            .map(|entry| (entry.sale_id, entry.item_id, entry.quantity, entry.line_total))
+            .collect();
+        assert_eq!(rows, vec![(1, first, 2, 20.0), (2, first, 1, 10.0), (2, second, 3, 15.0)]);
+        assert_eq!((ledger.total_sales, ledger.total_discount, ledger.count), (45.0, 0.0, 2));
+    }
+
+    #[test]
+    fn price_distribution_counts_each_band() {
+        let _env = setup();
+        for (n, price) in [5.0, 7.0, 15.0, 25.0, 29.0].into_iter().enumerate() {
+            add(&format!("Item {}", n), 1, price);
+        }
+        assert_eq!(price_distribution(10.0).unwrap(), vec![(0.0, 2), (10.0, 1), (20.0, 2)]);
+        assert!(price_distribution(0.0).is_err());
+    }
+
+    #[test]
+    fn reservations_reduce_availability_not_stock() {
+        let _env = setup();
+        let id = add("Tent", 10, 80.0);
+        reserve_item(id, 3).unwrap();
+        let availability = get_item_availability(id).unwrap();
+        assert_eq!(availability.item.quantity, 10);
+        assert_eq!((availability.reserved, availability.available), (3, 7));
+        assert!(matches!(record_sale(vec![(id, 8)], None), Err(BusinessError::InsufficientStock(_))));
+    }
+
+    #[test]
+    fn voided_sales_leave_revenue_but_stay_readable() {
+        let _env = setup();
+        let id = add("Radio", 10, 25.0);
+        sell(&[(id, 1)]);
+        let voided = sell(&[(id, 2)]);
+        void_sale(voided.seq, "Card declined".to_string()).unwrap();
+
+        assert_eq!(financial_overview().0, 25.0);
+        assert_eq!(quantity(id), 9);
+        let restored = stock_ledger(id, 0, time()).unwrap().pop().unwrap();
+        assert_eq!((restored.kind, restored.delta), (MovementKind::Void, 2));
+        let record = get_sale(voided.seq).unwrap();
+        assert_eq!(record.voided.unwrap().reason, "Card declined");
+        assert!(matches!(void_sale(voided.seq, "Again".to_string()), Err(BusinessError::Conflict(_))));
+    }
+
+    #[test]
+    fn duplicate_variants_are_rejected() {
+        let _env = setup();
+        let variant = |colour: &str| add_item("T-Shirt".to_string(), 5, 12.0, None, None, None, Some(colour.to_string()));
+        variant("Red").unwrap();
+        variant("Blue").unwrap();
+        assert!(matches!(variant("red"), Err(BusinessError::Conflict(_))));
+        assert_eq!(get_variants("T-Shirt".to_string()).len(), 2);
+    }
+
+    #[test]
+    fn sales_can_be_found_by_note() {
+        let _env = setup();
+        let id = add("Steak", 10, 30.0);
+        let noted = sell_with(
+            &[(id, 1)],
+            SaleOptions {
+                note: Some("Table 7, birthday".to_string()),
+                ..Default::default()
+            },
+        );
+        sell(&[(id, 1)]);
+        assert_eq!(seqs(&find_sales_by_note("BIRTHDAY".to_string())), vec![noted.seq]);
+        assert!(find_sales_by_note("anniversary".to_string()).is_empty());
+    }
+
+    #[test]
+    fn pending_sales_hold_stock_until_settled() {
+        let _env = setup();
+        let id = add("Sofa", 5, 300.0);
+        let pending = SaleOptions {
+            pending: Some(true),
+            ..Default::default()
+        };
+        let confirmed = sell_with(&[(id, 2)], pending.clone());
+        let cancelled = sell_with(&[(id, 1)], pending);
+        assert_eq!(quantity(id), 5);
+        assert_eq!(get_item_availability(id).unwrap().available, 2);
+        assert_eq!(pending_sales().len(), 2);
+
+        confirm_sale(confirmed.seq).unwrap();
+        assert_eq!(quantity(id), 3);
+        cancel_sale(cancelled.seq).unwrap();
+        assert_eq!(quantity(id), 3);
+        assert_eq!(get_item_availability(id).unwrap().available, 3);
+        assert!(pending_sales().is_empty());
+        assert_eq!(get_sale(cancelled.seq).unwrap().status, SaleStatus::Cancelled);
+    }
+
+    #[test]
+    fn sale_count_tracks_completed_sales() {
+        let _env = setup();
+        let id = add("Lamp", 20, 25.0);
+        let pending = SaleOptions {
+            pending: Some(true),
+            ..Default::default()
+        };
+        set_time(base_time() - 40 * DAY);
+        sell(&[(id, 1)]);
+        set_time(base_time());
+        let voided = sell(&[(id, 1)]);
+        let confirmed = sell_with(&[(id, 1)], pending.clone());
+        let cancelled = sell_with(&[(id, 1)], pending);
+        assert_eq!(get_sale_count(), 2);
+
+        confirm_sale(confirmed.seq).unwrap();
+        cancel_sale(cancelled.seq).unwrap();
+        assert_eq!(get_sale_count(), 3);
+        let snapshot = take_snapshot().unwrap();
+        void_sale(voided.seq, "Rang up twice".to_string()).unwrap();
+        assert_eq!(get_sale_count(), 2);
+        set_sales_retention(Some(30 * DAY)).unwrap();
+        assert_eq!(archive_old_sales().unwrap(), 1);
+        assert_eq!(get_sale_count(), 1);
+
+        rollback_to_snapshot(snapshot.id).unwrap();
+        assert_eq!(get_sale_count(), 3);
+        assert_eq!(get_sale_count(), SALES.with(|sales| sales.borrow().iter().count() as u64));
+    }
+
+    #[test]
+    fn rejected_sales_leave_no_seq_gap() {
+        let _env = setup();
+        let serialized = add("Phone", 1, 500.0);
+        add_serial(serialized, "SN-1".to_string()).unwrap();
+        set_item_serialized(serialized, true).unwrap();
+        let line = SaleLine {
+            serials: Some(vec!["SN-1".to_string()]),
+            ..SaleLine::from((serialized, 1))
+        };
+        let pending = SaleOptions {
+            pending: Some(true),
+            ..Default::default()
+        };
+        assert!(matches!(record_sale_lines(vec![line], Some(pending)), Err(BusinessError::InvalidInput(_))));
+
+        let id = add("Case", 5, 10.0);
+        assert!(record_sale(vec![(id, 10)], None).is_err());
+        assert_eq!(sell(&[(id, 1)]).seq, 1);
+        assert_eq!(sell(&[(id, 1)]).seq, 2);
+    }
+
+    #[test]
+    fn hourly_breakdown_slots_sales_by_hour() {
+        let _env = setup();
+        let id = add("Donut", 20, 1.5);
+        set_time(day_start(2024, 3, 15) + 9 * HOUR + 30 * MINUTE);
+        sell(&[(id, 2)]);
+        set_time(day_start(2024, 3, 15) + 14 * HOUR + 15 * MINUTE);
+        sell(&[(id, 4)]);
+        sell(&[(id, 1)]);
+
+        let hours = hourly_breakdown(base_time());
+        assert_eq!((hours[9].num_sales, hours[9].units, hours[9].revenue), (1, 2, 3.0));
+        assert_eq!((hours[14].num_sales, hours[14].units, hours[14].revenue), (2, 5, 7.5));
+        assert_eq!(hours.iter().map(|hour| hour.num_sales).sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn serial_numbers_can_only_be_sold_once() {
+        let _env = setup();
+        let id = add("Laptop", 2, 900.0);
+        add_serial(id, "SN-1".to_string()).unwrap();
+        add_serial(id, "SN-2".to_string()).unwrap();
+        assert!(matches!(add_serial(id, "SN-2".to_string()), Err(BusinessError::Conflict(_))));
+        set_item_serialized(id, true).unwrap();
+
+        let line = SaleLine {
+            serials: Some(vec!["SN-1".to_string()]),
+            ..SaleLine::from((id, 1))
+        };
+        let sale = record_sale_lines(vec![line.clone()], None).unwrap();
+        assert_eq!(sale.items[0].serials, vec!["SN-1".to_string()]);
+        assert!(matches!(record_sale_lines(vec![line], None), Err(BusinessError::Conflict(_))));
+
+        let sold: Vec<(String, SerialStatus, Option<u64>)> = get_serials(id)
+            .into_iter()
+            .map(|unit| (unit.serial, unit.status, unit.sale_seq))
+            .collect();
+        assert_eq!(
+            sold,
+            vec![
+                ("SN-1".to_string(), SerialStatus::Sold, Some(sale.seq)),
+                ("SN-2".to_string(), SerialStatus::InStock, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn markdowns_are_suggested_for_overstock_above_cost() {
+        let _env = setup();
+        let slow = add_costed("Slow", 100, 10.0, 5.0);
+        let fast = add_costed("Fast", 10, 10.0, 5.0);
+        sell(&[(slow, 1), (fast, 9)]);
+
+        let suggestions = suggest_markdowns(30).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        let suggestion = &suggestions[0];
+        assert_eq!(suggestion.item_id, slow);
+        assert!(suggestion.markdown_percent > 0.0);
+        assert!(suggestion.suggested_price >= 5.0 * (1.0 + DEFAULT_MARKDOWN_MIN_MARGIN_PERCENT / 100.0) - 1e-9);
+        assert!(suggestion.suggested_price < suggestion.current_price);
+    }
+
+    #[test]
+    fn tax_liability_splits_taxed_and_exempt_sales() {
+        let _env = setup();
+        set_tax_rate(10.0).unwrap();
+        let taxed = add("Taxed", 10, 10.0);
+        let exempt = add("Exempt", 10, 5.0);
+        set_item_tax_exempt(exempt, true).unwrap();
+        sell(&[(taxed, 2)]);
+        sell(&[(exempt, 1)]);
+
+        let liability = tax_liability(0, time()).unwrap();
+        assert_eq!(
+            (liability.taxable_sales, liability.tax_collected, liability.exempt_sales),
+            (20.0, 2.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn sales_below_the_minimum_are_rejected() {
+        let _env = setup();
+        let id = add("Stamp", 100, 1.0);
+        set_min_sale_amount(10.0).unwrap();
+        assert_eq!(
+            record_sale(vec![(id, 5)], None).unwrap_err(),
+            BusinessError::BelowMinimumSale { total: 5.0, minimum: 10.0 }
+        );
+        sell(&[(id, 15)]);
+        let overridden = SaleOptions {
+            override_minimum: Some(true),
+            ..Default::default()
+        };
+        sell_with(&[(id, 1)], overridden);
+        assert_eq!(quantity(id), 84);
+    }
+
+    #[test]
+    fn frequently_bought_together_ranks_co_occurrence() {
+        let _env = setup();
+        let bread = add("Bread", 20, 2.0);
+        let butter = add("Butter", 20, 3.0);
+        let jam = add("Jam", 20, 4.0);
+        let milk = add("Milk", 20, 1.0);
+        sell(&[(bread, 1), (butter, 1), (jam, 1)]);
+        sell(&[(bread, 1), (butter, 1)]);
+        sell(&[(bread, 1), (jam, 1)]);
+        sell(&[(bread, 1), (butter, 2), (butter, 1)]);
+        sell(&[(milk, 1), (jam, 1)]);
+        assert_eq!(frequently_bought_together(bread, 5), vec![(butter, 3), (jam, 2)]);
+        assert_eq!(frequently_bought_together(bread, 1), vec![(butter, 3)]);
+    }
+
+    #[test]
+    fn sale_totals_verify_until_tampered_with() {
+        let _env = setup();
+        let id = add("Rug", 10, 40.0);
+        sell(&[(id, 1)]);
+        let sale = sell(&[(id, 2)]);
+        assert!(verify_sale_totals().is_empty());
+
+        let mut tampered = get_sale(sale.seq).unwrap();
+        tampered.total_amount = 10.0;
+        SALES.with(|sales| sales.borrow_mut().insert(&tampered));
+        assert_eq!(verify_sale_totals(), vec![sale.seq]);
+    }
+
+    #[test]
+    fn api_descriptor_lists_every_exported_method() {
+        let _env = setup();
+        let did = __export_service();
+        let service = &did[did.find("service :").unwrap()..];
+        let exported: BTreeSet<String> = service
+            .lines()
+            .skip(1)
+            .filter(|line| line.starts_with("  ") && !line.starts_with("   "))
+            .filter_map(|line| line.trim().split_once(" : "))
+            .map(|(name, _)| name.trim_matches('"').to_string())
+            .collect();
+        let described: BTreeSet<String> = api_descriptor().into_iter().map(|method| method.name).collect();
+        assert!(exported.len() > 100);
+        assert_eq!(described, exported);
+    }
+
+    #[test]
+    fn online_buffer_only_limits_online_availability() {
+        let _env = setup();
+        let id = add("Bike", 10, 200.0);
+        set_online_buffer(id, Some(4)).unwrap();
+        assert_eq!(online_availability(id), 6);
+        assert_eq!(get_item_availability(id).unwrap().online_available, 6);
+        sell(&[(id, 8)]);
+        assert_eq!(online_availability(id), 0);
+        assert!(get_available_inventory(0, 10).items.is_empty());
+    }
+
+    #[test]
+    fn below_cost_sales_report_their_loss() {
+        let _env = setup();
+        let id = add_costed("Blender", 10, 10.0, 6.0);
+        sell(&[(id, 1)]);
+        let leak = record_sale_lines(vec![discounted(id, 2, 5.0, DiscountReason::Damaged)], None).unwrap();
+
+        let leaks = below_cost_sales(0, time()).unwrap();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].0.seq, leaks[0].1), (leak.seq, 2.0));
+    }
+
+    #[test]
+    fn ean13_barcodes_are_checked() {
+        let _env = setup();
+        set_barcode_format(Some(BarcodeFormat::Ean13)).unwrap();
+        let with_sku = |sku: &str| add_item("Biscuits".to_string(), 5, 2.0, Some(sku.to_string()), None, None, None);
+        assert!(matches!(with_sku("4006381333932"), Err(BusinessError::InvalidBarcode(_))));
+        assert!(matches!(with_sku("400638133393"), Err(BusinessError::InvalidBarcode(_))));
+        assert!(matches!(with_sku("400638133393X"), Err(BusinessError::InvalidBarcode(_))));
+        assert!(with_sku("4006381333931").is_ok());
+        assert!(check_barcode("4006381333931").is_ok());
+    }
+
+    #[test]
+    fn stock_alerts_fire_once_per_crossing() {
+        let _env = setup();
+        let id = add("Eggs", 10, 0.3);
+        set_reorder_levels(id, Some(5), None).unwrap();
+        sell(&[(id, 6)]);
+        sell(&[(id, 1)]);
+        restock_item(id, 5, None).unwrap();
+        restock_item(id, 5, None).unwrap();
+
+        let alerts: Vec<(StockAlertKind, u64)> = get_stock_alerts(0)
+            .into_iter()
+            .map(|alert| (alert.kind, alert.quantity))
+            .collect();
+        assert_eq!(alerts, vec![(StockAlertKind::LowStock, 4), (StockAlertKind::BackInStock, 8)]);
+    }
+
+    #[test]
+    fn employee_productivity_counts_active_hours() {
+        let _env = setup();
+        let id = add("Ticket", 50, 10.0);
+        set_caller(USER);
+        for at in [10 * HOUR + 5 * MINUTE, 10 * HOUR + 40 * MINUTE, 14 * HOUR + 10 * MINUTE] {
+            set_time(day_start(2024, 3, 15) + at);
+            sell(&[(id, 1)]);
+        }
+        set_caller(CONTROLLER);
+        sell(&[(id, 1)]);
+
+        let productivity = employee_productivity(0, time()).unwrap();
+        let cashier = productivity.iter().find(|row| row.principal == USER).unwrap();
+        assert_eq!(
+            (cashier.hours_with_sales, cashier.total_revenue, cashier.revenue_per_active_hour),
+            (2, 30.0, 15.0)
+        );
+        assert_eq!(productivity.len(), 2);
+    }
+
+    #[test]
+    fn archiving_keeps_lifetime_revenue() {
+        let _env = setup();
+        let id = add("Poster", 20, 5.0);
+        set_time(base_time() - 40 * DAY);
+        sell(&[(id, 2)]);
+        set_time(base_time());
+        let kept = sell(&[(id, 1)]);
+        let before = financial_overview();
+
+        set_sales_retention(Some(30 * DAY)).unwrap();
+        assert_eq!(archive_old_sales().unwrap(), 1);
+        assert_eq!(seqs(&get_sales()), vec![kept.seq]);
+        assert_eq!(financial_overview(), before);
+        let archived = get_archived_summary();
+        assert_eq!(archived.len(), 1);
+        assert_eq!((archived[0].num_sales, archived[0].revenue), (1, 10.0));
+
+        set_caller(USER);
+        assert!(matches!(set_sales_retention(None), Err(BusinessError::Unauthorized(_))));
+        assert!(matches!(archive_old_sales(), Err(BusinessError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn patches_change_only_the_given_fields() {
+        let _env = setup();
+        let id = add_item("Cup".to_string(), 5, 3.0, Some("CUP-1".to_string()), None, None, None).unwrap();
+        patch_item(
+            id,
+            ItemPatch {
+                name: Some("  Tea   Cup ".to_string()),
+                price: Some(3.5),
+                category: Some("Kitchen".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let item = get_item_details(id).unwrap();
+        assert_eq!((item.name.as_str(), item.price, item.category.as_deref()), ("Tea Cup", 3.5, Some("Kitchen")));
+        assert_eq!((item.quantity, item.sku.as_deref(), item.cost_price), (5, Some("CUP-1"), None));
+
+        let blank = ItemPatch {
+            name: Some("   ".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(patch_item(id, blank), Err(BusinessError::InvalidInput(_))));
+        assert_eq!(get_item_details(id).unwrap().name, "Tea Cup");
+    }
+
+    #[test]
+    fn price_simulation_reports_the_new_margin() {
+        let _env = setup();
+        let id = add_costed("Hat", 20, 10.0, 6.0);
+        sell(&[(id, 3)]);
+
+        let simulation = simulate_price_change(id, 12.0).unwrap();
+        assert!((simulation.current_margin_percent - 40.0).abs() < 1e-9);
+        assert!((simulation.simulated_margin_percent - 50.0).abs() < 1e-9);
+        assert_eq!(simulation.simulated_unit_margin, 6.0);
+        assert_eq!(simulation.projected_profit_change, Some(6.0));
+        assert_eq!(get_item_details(id).unwrap().price, 10.0);
+    }
+
+    #[test]
+    fn repeated_return_lines_are_merged() {
+        let _env = setup();
+        let id = add("Glass", 10, 4.0);
+        let sale = sell(&[(id, 3)]);
+        assert!(return_sale_items(sale.seq, vec![(id, 2), (id, 2)], ReturnReason::Defective).is_err());
+        assert_eq!(quantity(id), 7);
+
+        let record = return_sale_items(sale.seq, vec![(id, 1), (id, 1)], ReturnReason::Defective).unwrap();
+        assert_eq!(record.items.len(), 1);
+        assert_eq!(record.items[0].quantity, 2);
+        assert_eq!(record.refund_amount, 8.0);
+        assert_eq!(quantity(id), 9);
+    }
+
+    #[test]
+    fn catalog_version_moves_only_on_writes() {
+        let _env = setup();
+        let before = get_catalog_version();
+        add("Fork", 10, 1.0);
+        let after = get_catalog_version();
+        assert!(after > before);
+        get_inventory();
+        search_items("fork".to_string());
+        financial_overview();
+        assert_eq!(get_catalog_version(), after);
+    }
+
+    #[test]
+    fn deposits_are_charged_separately_and_refunded() {
+        let _env = setup();
+        let id = add("Bottled Water", 10, 2.0);
+        set_item_deposit(id, Some(0.25)).unwrap();
+        let sale = sell(&[(id, 4)]);
+        assert_eq!((sale.total_amount, sale.deposit_total), (8.0, 1.0));
+        assert_eq!(outstanding_deposits(), 1.0);
+
+        assert_eq!(refund_deposit(sale.seq, id, 3).unwrap(), 0.75);
+        assert!(refund_deposit(sale.seq, id, 2).is_err());
+        assert_eq!(refund_deposit(sale.seq, id, 1).unwrap(), 0.25);
+        assert_eq!(outstanding_deposits(), 0.0);
+        assert_eq!(financial_overview().0, 8.0);
+    }
+
+    #[test]
+    fn revenue_is_split_by_channel() {
+        let _env = setup();
+        let id = add("Book", 20, 10.0);
+        sell(&[(id, 1)]);
+        let online = |channel| SaleOptions {
+            channel: Some(channel),
+            ..Default::default()
+        };
+        sell_with(&[(id, 2)], online(Channel::Online));
+        sell_with(&[(id, 3)], online(Channel::Online));
+        sell_with(&[(id, 1)], online(Channel::Other("Market Stall".to_string())));
+
+        assert_eq!(
+            revenue_by_channel(0, time()).unwrap(),
+            vec![
+                (Channel::InStore, 10.0),
+                (Channel::Online, 50.0),
+                (Channel::Other("Market Stall".to_string()), 10.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn deleted_items_can_be_recovered_from_sales() {
+        let _env = setup();
+        let id = add_in_category("Teapot", 5, 18.0, "Kitchen");
+        sell(&[(id, 1)]);
+        remove_item(id).unwrap();
+
+        let recovered = recover_item(id).unwrap();
+        assert_ne!(recovered, id);
+        let item = get_item_details(recovered).unwrap();
+        assert_eq!((item.name.as_str(), item.price, item.quantity), ("Teapot", 18.0, 0));
+        assert_eq!(item.category.as_deref(), Some("Kitchen"));
+        assert_eq!(item_sales_report(recovered, 0, time()).unwrap().units_sold, 1);
+        assert!(matches!(recover_item(recovered), Err(BusinessError::Conflict(_))));
+        assert!(recover_item(999).is_err());
+    }
+
+    #[test]
+    fn daily_deal_is_stable_within_a_day() {
+        let _env = setup();
+        DEAL_SEED.with(|seed| seed.set(Some(42)));
+        for n in 0..8 {
+            add(&format!("Item {}", n), 10 + n, 1.0);
+        }
+        let today = local_day_number(time());
+        let deal = deal_of_the_day().unwrap().id;
+        refresh_daily_deal();
+        advance(6 * HOUR);
+        assert_eq!(deal_of_the_day().unwrap().id, deal);
+        assert_eq!(pick_daily_deal(today), Some(deal));
+
+        let picks: BTreeSet<ItemId> = (today..today + 14).filter_map(pick_daily_deal).collect();
+        assert!(picks.len() > 1);
+        advance(DAY);
+        refresh_daily_deal();
+        assert_eq!(DAILY_DEAL.with(Cell::get), Some((today + 1, pick_daily_deal(today + 1))));
+    }
+
+    #[test]
+    fn split_sales_divide_lines_and_totals() {
+        let _env = setup();
+        let first = add("Shirt", 10, 10.0);
+        let second = add("Socks", 10, 5.0);
+        let sale = sell(&[(first, 2), (second, 1)]);
+
+        let (kept, split) = split_sale(sale.seq, vec![(second, 1)]).unwrap();
+        assert_eq!(kept, sale.seq);
+        let kept = get_sale(kept).unwrap();
+        let split = get_sale(split).unwrap();
+        assert_eq!((kept.items.len(), kept.items[0].id, kept.total_amount), (1, first, 20.0));
+        assert_eq!((split.items.len(), split.items[0].id, split.total_amount), (1, second, 5.0));
+        assert_eq!((quantity(first), quantity(second)), (8, 9));
+        assert!(verify_sale_totals().is_empty());
+    }
+
+    #[test]
+    fn zero_decimal_currencies_reject_fractions() {
+        let _env = setup();
+        set_currency("JPY".to_string(), 0).unwrap();
+        assert!(matches!(
+            add_item("Ramen".to_string(), 5, 850.5, None, None, None, None),
+            Err(BusinessError::InvalidInput(_))
+        ));
+        let id = add("Ramen", 5, 850.0);
+        assert_eq!(format_amount(1700.0), "1700");
+        assert_eq!(sell(&[(id, 2)]).total_amount, 1700.0);
+
+        set_currency("USD".to_string(), 2).unwrap();
+        add("Priced", 1, 9.99);
+        assert!(matches!(set_currency("JPY".to_string(), 0), Err(BusinessError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn cart_reservations_are_all_or_nothing() {
+        let _env = setup();
+        let plenty = add("Plenty", 5, 1.0);
+        let short = add("Short", 3, 1.0);
+        assert!(matches!(
+            reserve_cart(vec![(plenty, 2), (short, 10)]),
+            Err(BusinessError::InsufficientStock(_))
+        ));
+        assert!(get_reservations().is_empty());
+        assert_eq!(get_item_availability(plenty).unwrap().available, 5);
+
+        reserve_cart(vec![(plenty, 2), (short, 3)]).unwrap();
+        assert_eq!(get_reservations()[0].items, vec![(plenty, 2), (short, 3)]);
+    }
+
+    #[test]
+    fn trends_compare_consecutive_windows() {
+        let _env = setup();
+        let id = add("Sunscreen", 20, 8.0);
+        set_time(base_time() - 10 * DAY);
+        sell(&[(id, 2)]);
+        set_time(base_time());
+        sell(&[(id, 5)]);
+
+        let trend = item_trend(id, 7 * DAY).unwrap();
+        assert_eq!(trend.direction, TrendDirection::Rising);
+        assert_eq!((trend.current_units, trend.prior_units), (5, 2));
+        assert_eq!(trend.percent_change, Some(150.0));
+        assert_eq!(item_trend(add("New", 1, 1.0), 7 * DAY).unwrap().direction, TrendDirection::NoData);
+    }
+
+    #[test]
+    fn deleting_an_item_cleans_up_references() {
+        let _env = setup();
+        let featured = add("Featured", 5, 1.0);
+        let held = add("Held", 5, 1.0);
+        set_featured(vec![featured, held]).unwrap();
+        reserve_cart(vec![(featured, 1), (held, 1)]).unwrap();
+        remove_item(featured).unwrap();
+        assert_eq!(get_config().featured_items, vec![held]);
+        assert_eq!(get_reservations()[0].items, vec![(held, 1)]);
+
+        let pending = SaleOptions {
+            pending: Some(true),
+            ..Default::default()
+        };
+        sell_with(&[(held, 1)], pending);
+        assert!(matches!(remove_item(held), Err(BusinessError::Conflict(_))));
+    }
+
+    #[test]
+    fn zero_stock_items_exist_but_cannot_be_sold() {
+        let _env = setup();
+        assert!(matches!(
+            add_item("Preorder".to_string(), 0, 60.0, None, None, None, None),
+            Err(BusinessError::ZeroQuantity(_))
+        ));
+        set_allow_zero_initial_stock(true).unwrap();
+        let id = add("Preorder", 0, 60.0);
+        assert_eq!(quantity(id), 0);
+        assert!(matches!(record_sale(vec![(id, 1)], None), Err(BusinessError::InsufficientStock(_))));
+        restock_item(id, 2, None).unwrap();
+        sell(&[(id, 1)]);
+    }
+
+    #[test]
+    fn discounts_are_reported_by_reason() {
+        let _env = setup();
+        let id = add("Jacket", 20, 100.0);
+        record_sale_lines(vec![discounted(id, 2, 80.0, DiscountReason::Promotion)], None).unwrap();
+        record_sale_lines(vec![discounted(id, 1, 90.0, DiscountReason::Loyalty)], None).unwrap();
+        record_sale_lines(vec![discounted(id, 1, 70.0, DiscountReason::Promotion)], None).unwrap();
+        sell(&[(id, 1)]);
+        assert!(matches!(
+            record_sale_lines(vec![SaleLine { override_price: Some(50.0), ..SaleLine::from((id, 1)) }], None),
+            Err(BusinessError::InvalidInput(_))
+        ));
+
+        assert_eq!(
+            discount_report(0, time()).unwrap(),
+            vec![(DiscountReason::Promotion, 70.0, 2), (DiscountReason::Loyalty, 10.0, 1)]
+        );
+    }
+
+    #[test]
+    fn stock_ledger_tracks_running_balances() {
+        let _env = setup();
+        let id = add("Nails", 10, 0.1);
+        sell(&[(id, 3)]);
+        restock_item(id, 5, None).unwrap();
+        update_quantity_cas(id, 12, 10).unwrap();
+
+        let rows: Vec<(MovementKind, i64, u64)> = stock_ledger(id, 0, time())
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.kind, entry.delta, entry.balance_after))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (MovementKind::Initial, 10, 10),
+                (MovementKind::Sale, -3, 7),
+                (MovementKind::Restock, 5, 12),
+                (MovementKind::Adjustment, -2, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn refunds_match_the_discounted_price_paid() {
+        let _env = setup();
+        let id = add("Boots", 10, 10.0);
+        let sale = record_sale_lines(vec![discounted(id, 2, 8.0, DiscountReason::Promotion)], None).unwrap();
+        let record = return_sale_items(sale.seq, vec![(id, 1)], ReturnReason::WrongItem).unwrap();
+        assert_eq!(record.refund_amount, 8.0);
+        assert!(matches!(
+            return_sale_items(sale.seq, vec![(id, 2)], ReturnReason::WrongItem),
+            Err(BusinessError::RefundExceedsPayment(_))
+        ));
+    }
+
+    #[test]
+    fn init_args_set_the_config_and_owner() {
+        let _env = setup();
+        init(Some(InitArgs {
+            owner: Some(USER),
+            currency: Some(CurrencyConfig {
+                code: "eur".to_string(),
+                decimal_places: 2,
+            }),
+            tax_rate_percent: Some(7.5),
+        }));
+        let config = get_config();
+        assert_eq!(config.owner, Some(USER));
+        assert_eq!(config.currency.code, "EUR");
+        assert_eq!(config.tax_rate_percent, 7.5);
+
+        set_caller(USER);
+        assert!(set_tax_rate(5.0).is_ok());
+        set_caller(OTHER_USER);
+        assert!(matches!(set_tax_rate(5.0), Err(BusinessError::Unauthorized(_))));
+        let anonymous_owner = InitArgs {
+            owner: Some(Principal::anonymous()),
+            currency: None,
+            tax_rate_percent: None,
+        };
+        assert!(matches!(apply_init_args(anonymous_owner), Err(BusinessError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn available_inventory_lists_only_sellable_items() {
+        let _env = setup();
+        set_allow_zero_initial_stock(true).unwrap();
+        let banana = add("banana", 3, 1.0);
+        add("Cherry", 0, 1.0);
+        let inactive = add("Date", 4, 1.0);
+        set_item_active(inactive, false).unwrap();
+        let apple = add("Apple", 2, 1.0);
+
+        let page = get_available_inventory(0, 10);
+        assert_eq!(page.total, 2);
+        assert_eq!(ids(&page.items), vec![apple, banana]);
+        assert_eq!(ids(&get_available_inventory(1, 10).items), vec![banana]);
+    }
+
+    #[test]
+    fn best_effort_sales_skip_short_lines() {
+        let _env = setup();
+        let plenty = add("Plenty", 5, 2.0);
+        let short = add("Short", 2, 3.0);
+        let options = SaleOptions {
+            partial_fulfillment: Some(true),
+            ..Default::default()
+        };
+        let sale = sell_with(&[(plenty, 1), (short, 5)], options.clone());
+        assert_eq!((sale.items.len(), sale.items[0].id, sale.total_amount), (1, plenty, 2.0));
+        assert_eq!(sale.skipped, vec![(short, 5)]);
+        assert_eq!((quantity(plenty), quantity(short)), (4, 2));
+        assert!(matches!(record_sale(vec![(short, 5)], Some(options)), Err(BusinessError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn best_effort_sales_skip_lines_that_cannot_be_sold() {
+        let _env = setup();
+        let plenty = add("Plenty", 5, 2.0);
+        let guarded = add("Guarded", 5, 3.0);
+        let inactive = add("Inactive", 5, 4.0);
+        set_safety_stock(guarded, Some(3)).unwrap();
+        set_item_active(inactive, false).unwrap();
+        let options = SaleOptions {
+            partial_fulfillment: Some(true),
+            ..Default::default()
+        };
+        let sale = sell_with(&[(plenty, 1), (guarded, 3), (inactive, 1)], options);
+        assert_eq!((sale.items.len(), sale.items[0].id), (1, plenty));
+        assert_eq!(sale.skipped, vec![(guarded, 3), (inactive, 1)]);
+        assert_eq!((quantity(guarded), quantity(inactive)), (5, 5));
+    }
+
+    #[test]
+    fn rollback_restores_a_snapshot() {
+        let _env = setup();
+        let first = add("First", 5, 1.0);
+        let initial = take_snapshot().unwrap();
+        let second = add("Second", 5, 1.0);
+        sell(&[(second, 2)]);
+        let later = take_snapshot().unwrap();
+        add("Third", 5, 1.0);
+        assert_eq!(list_snapshots().len(), 2);
+
+        rollback_to_snapshot(initial.id).unwrap();
+        assert_eq!(ids(&get_inventory()), vec![first]);
+        assert!(get_sales().is_empty());
+
+        rollback_to_snapshot(later.id).unwrap();
+        assert_eq!(ids(&get_inventory()), vec![first, second]);
+        assert_eq!(quantity(second), 3);
+        assert_eq!(get_sales().len(), 1);
+        assert!(rollback_to_snapshot(99).is_err());
+    }
+
+    #[test]
+    fn minimum_margin_is_enforced_on_every_price_path() {
+        let _env = setup();
+        let id = add_costed("Speaker", 10, 20.0, 6.0);
+        let below_cost = apply_price_list(vec![(id, 5.0)], true).unwrap();
+        assert_eq!(below_cost.outcomes[0].applied_price, Some(5.0));
+        set_min_margin(Some(50.0), MarginPolicy::Reject).unwrap();
+        let too_low = |result: Result<(), BusinessError>| matches!(result, Err(BusinessError::MarginTooLow(_)));
+
+        let patch = ItemPatch {
+            price: Some(10.0),
+            ..Default::default()
+        };
+        assert!(too_low(patch_item(id, patch)));
+        let report = apply_price_list(vec![(id, 10.0)], false).unwrap();
+        assert!(too_low(report.outcomes[0].rejection.clone().map_or(Ok(()), Err)));
+        assert!(too_low(
+            record_sale_lines(vec![discounted(id, 1, 10.0, DiscountReason::Manager("Ann".to_string()))], None).map(drop)
+        ));
+        assert_eq!(get_item_details(id).unwrap().price, 20.0);
+
+        set_min_margin(Some(50.0), MarginPolicy::Cap).unwrap();
+        let sale = record_sale_lines(vec![discounted(id, 1, 10.0, DiscountReason::Promotion)], None).unwrap();
+        assert_eq!(sale.items[0].unit_price, 12.0);
+    }
+
+    #[test]
+    fn every_error_variant_round_trips_through_candid() {
+        let variants = vec![
+            BusinessError::NotFound("missing".to_string()),
+            BusinessError::InvalidInput("bad".to_string()),
+            BusinessError::Unauthorized("nope".to_string()),
+            BusinessError::InsufficientStock("short".to_string()),
+            BusinessError::Conflict("clash".to_string()),
+            BusinessError::MarginTooLow("thin".to_string()),
+            BusinessError::RefundExceedsPayment("too much".to_string()),
+            BusinessError::ZeroQuantity("zero".to_string()),
+            BusinessError::NothingToUpdate,
+            BusinessError::TooManyLines { max: 500 },
+            BusinessError::ConcurrentModification { current: 7 },
+            BusinessError::ExceedsCapacity { max: 10, attempted: 11 },
+            BusinessError::ValueTooLarge("huge".to_string()),
+            BusinessError::InvalidTimeRange("backwards".to_string()),
+            BusinessError::InvalidBarcode("checksum".to_string()),
+            BusinessError::BelowMinimumSale { total: 5.0, minimum: 10.0 },
+            BusinessError::RateLimited { retry_after: 30 },
+        ];
+        for error in variants {
+            let bytes = Encode!(&error).unwrap();
+            assert_eq!(Decode!(&bytes, BusinessError).unwrap(), error);
+            let result: ApiResult<u64> = Err(error);
+            let bytes = Encode!(&result).unwrap();
+            assert_eq!(Decode!(&bytes, ApiResult<u64>).unwrap(), result);
+        }
+    }
+}